@@ -1,569 +1,250 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
-use slab::Slab;
-use stable_vec::StableVec;
-
-// Define a trait that abstracts over both Slab and StableVec operations
-trait Slabbable<T: Default> {
-    #[inline(always)]
-    fn new_with_capacity(capacity: usize) -> Self where Self: Sized;
-    
-    #[inline(always)]
-    fn insert(&mut self, value: T) -> usize;
-    
-    #[inline(always)]
-    fn insert_at(&mut self, key: usize, value: T) -> Option<T>;
-    
-    #[inline(always)]
-    fn remove(&mut self, key: usize) -> Option<T>;
-    
-    #[inline(always)]
-    fn get(&self, key: usize) -> Option<&T>;
-    
-    #[inline(always)]
-    fn contains(&self, key: usize) -> bool;
-}
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-// Wrapper type for Slab to implement our trait
-struct SlabWrapper<T>(Slab<T>);
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use slabbench::audit;
+use slabbench::backend::{visit_backend, BackendVisitor, Builtin, Slabbable, BACKENDS};
+use slabbench::history::{self, Record};
+use slabbench::isolation;
+use slabbench::payload::{visit_payload, Payload, PayloadVisitor};
+use slabbench::phase::Phases;
+use slabbench::results;
+use slabbench::run::format_ns;
+use slabbench::scenario::{self, Case, Scenario};
+use slabbench::trace::Trace;
+use slabbench::workload::Workload;
 
-impl<T: Default> Slabbable<T> for SlabWrapper<T> {
-    #[inline(always)]
-    fn new_with_capacity(capacity: usize) -> Self {
-        Self(Slab::with_capacity(capacity))
-    }
-    
-    #[inline(always)]
-    fn insert(&mut self, value: T) -> usize {
-        self.0.insert(value)
-    }
-    
-    #[inline(always)]
-    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
-        let old_value = if self.0.contains(key) {
-            Some(self.0.remove(key))
-        } else {
-            None
-        };
-        
-        // Grow the slab if needed
-        while self.0.capacity() <= key {
-            self.0.reserve(key - self.0.capacity() + 1);
-        }
-        
-        // Now use a vacant entry to get a key
-        let vacant = self.0.vacant_entry();
-        
-        // If the key is not what we want, we need a more complex solution
-        if vacant.key() != key {
-            // Insert the value at whatever key we got
-            let temp_key = self.0.insert(value);
-            
-            // If we got a different key than desired, we need to set up the desired key
-            if temp_key != key {
-                // Fill all slots up to and including our desired key with vacant entries
-                // This essentially "reserves" the slots
-                for i in self.0.capacity()..=key {
-                    self.0.insert(Default::default());
-                }
-                
-                // Remove the temporary value
-                let temp_value = self.0.remove(temp_key);
-                
-                // Now that we have ensured the exact key exists and is vacant,
-                // we can insert our value there
-                self.0.insert(temp_value);
-            }
-        } else {
-            // The vacant entry key matches what we want - easy case
-            vacant.insert(value);
-        }
-        
-        old_value
-    }
-    
-    #[inline(always)]
-    fn remove(&mut self, key: usize) -> Option<T> {
-        self.0.try_remove(key)
-    }
-    
-    #[inline(always)]
-    fn get(&self, key: usize) -> Option<&T> {
-        self.0.get(key)
-    }
-    
-    #[inline(always)]
-    fn contains(&self, key: usize) -> bool {
-        self.0.contains(key)
+#[cfg(feature = "count-alloc")]
+#[global_allocator]
+static ALLOC: slabbench::alloc::CountingAlloc = slabbench::alloc::CountingAlloc;
+
+/// Write `contents` to `name` next to Criterion's results for a benchmark, in
+/// `target/criterion/<group>/<id>/<parameter>/`
+fn write_report(group: &str, id: &str, parameter: &str, name: &str, contents: String) {
+    let dir = results::criterion_dir().join(group).join(id).join(parameter);
+    let written = std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(name), contents));
+    if let Err(e) = written {
+        eprintln!("failed to write {}: {}", dir.join(name).display(), e);
     }
 }
 
-// Wrapper type for StableVec to implement our trait
-struct StableVecWrapper<T>(StableVec<T>);
+/// Run a workload once outside of timing and report its heap usage next to
+/// Criterion's results, in `alloc.json`
+#[cfg(feature = "count-alloc")]
+fn report_alloc<R>(group: &str, id: &str, parameter: &str, run: impl FnOnce() -> R) {
+    let (result, stats) = slabbench::alloc::measure(run);
+    drop(result);
+    eprintln!("{}/{}/{}: {}", group, id, parameter, stats);
+    write_report(group, id, parameter, "alloc.json", stats.to_json());
+}
 
-impl<T: Default> Slabbable<T> for StableVecWrapper<T> {
-    #[inline(always)]
-    fn new_with_capacity(capacity: usize) -> Self {
-        Self(StableVec::with_capacity(capacity))
-    }
-    
-    #[inline(always)]
-    fn insert(&mut self, value: T) -> usize {
-        self.0.push(value)
-    }
-    
-    #[inline(always)]
-    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
-        self.0.insert(key, value)
-    }
-    
-    #[inline(always)]
-    fn remove(&mut self, key: usize) -> Option<T> {
-        self.0.remove(key)
-    }
-    
-    #[inline(always)]
-    fn get(&self, key: usize) -> Option<&T> {
-        self.0.get(key)
-    }
-    
-    #[inline(always)]
-    fn contains(&self, key: usize) -> bool {
-        self.0.has_element_at(key)
+#[cfg(not(feature = "count-alloc"))]
+fn report_alloc<R>(_group: &str, _id: &str, _parameter: &str, _run: impl FnOnce() -> R) {}
+
+/// Report the mean time per iteration of each phase over every iteration
+/// Criterion ran, next to Criterion's results in `phases.json`
+fn report_phases(group: &str, id: &str, parameter: &str, phases: &Phases) {
+    let summaries = phases.summaries();
+    let breakdown: Vec<String> = summaries
+        .iter()
+        .map(|phase| format!("{} {} ({:.1}%)", phase.phase, format_ns(phase.mean_ns as f64), phase.share * 100.0))
+        .collect();
+    eprintln!("{}/{}/{}: {}", group, id, parameter, breakdown.join(", "));
+
+    let json = serde_json::to_string_pretty(&summaries).expect("phase times are serializable");
+    write_report(group, id, parameter, "phases.json", json);
+}
+
+/// Benchmarks one case with its payload on one backend
+struct BenchCase<'a, 'g> {
+    group: &'a mut BenchmarkGroup<'g, WallTime>,
+    group_name: &'a str,
+    case: &'a Case,
+    backend: &'a str,
+    parameter: &'a str,
+    size: usize,
+    /// Operations one iteration performs on this backend
+    ops: u64,
+}
+
+impl PayloadVisitor for BenchCase<'_, '_> {
+    type Output = Option<()>;
+
+    fn visit<T: Payload>(self, _name: &'static str) -> Option<()> {
+        visit_backend::<T, _>(self.backend, self)
     }
 }
 
-// Mixed Workload Benchmarks
-// These are the most important benchmarks as they simulate real-world usage
-
-fn bench_standard_mixed_workload(c: &mut Criterion) {
-    let mut group = c.benchmark_group("standard_mixed_workload");
-    group.sample_size(20); // Reduce sample size to make benchmarks run faster
-    
-    // Use fewer size variants but include a larger size for stress testing
-    for size in [1_000, 10_000, 100_000].iter() {
-        group.throughput(Throughput::Elements(*size as u64));
-        
-        group.bench_with_input(BenchmarkId::new("slab", size), size, |b, size| {
-            b.iter(|| {
-                let mut slab = Slab::with_capacity(*size / 2);
-                let mut keys = Vec::with_capacity(*size);
-                
-                // Insert phase
-                for i in 0..*size {
-                    keys.push(slab.insert(i));
-                }
-                
-                // Get phase
-                let mut sum = 0;
-                for &key in &keys {
-                    if let Some(&val) = slab.get(key) {
-                        sum += val;
-                    }
-                }
-                black_box(sum);
-                
-                // Remove every third element
-                for i in (0..keys.len()).step_by(3) {
-                    slab.remove(keys[i]);
-                }
-                
-                // Insert some new elements
-                for i in 0..(*size / 4) {
-                    slab.insert(i * 100);
-                }
-                
-                // Final get phase
-                sum = 0;
-                for (_, &val) in slab.iter() {
-                    sum += val;
-                }
-                black_box(sum)
+impl<T: Payload> BackendVisitor<T> for BenchCase<'_, '_> {
+    type Output = ();
+
+    fn visit<S: Slabbable<T>>(self, name: &'static str) {
+        let id = self.case.id(name);
+        let (workload, size, seed) = (&self.case.workload, self.size, self.case.seed);
+
+        // Operation counts can differ between backends, so set them per function
+        self.group.throughput(Throughput::Elements(self.ops.max(1)));
+
+        // Time each iteration by hand so the phases it reports add up to
+        // what Criterion measures
+        let mut phases = Phases::disabled();
+        self.group.bench_function(BenchmarkId::new(&id, self.parameter), |b| {
+            b.iter_custom(|iters| {
+                // Setup and dropping the final structure are excluded
+                (0..iters).map(|_| workload.run_timed::<T, S>(size, seed, &mut phases)).sum::<Duration>()
             })
         });
-        
-        group.bench_with_input(BenchmarkId::new("stable_vec", size), size, |b, size| {
-            b.iter(|| {
-                let mut sv = StableVec::with_capacity(*size / 2);
-                let mut keys = Vec::with_capacity(*size);
-                
-                // Insert phase
-                for i in 0..*size {
-                    keys.push(sv.push(i));
-                }
-                
-                // Get phase
-                let mut sum = 0;
-                for &key in &keys {
-                    if let Some(&val) = sv.get(key) {
-                        sum += val;
-                    }
-                }
-                black_box(sum);
-                
-                // Remove every third element
-                for i in (0..keys.len()).step_by(3) {
-                    sv.remove(keys[i]);
-                }
-                
-                // Insert some new elements
-                for i in 0..(*size / 4) {
-                    sv.push(i * 100);
-                }
-                
-                // Final get phase
-                sum = 0;
-                for (_, &val) in sv.iter() {
-                    sum += val;
-                }
-                black_box(sum)
-            })
+        report_phases(self.group_name, &id, self.parameter, &phases);
+        report_alloc(self.group_name, &id, self.parameter, || {
+            workload.run::<T, S>(workload.setup::<T, S>(size, seed), size)
         });
     }
-    
-    group.finish();
 }
 
-/// Generic benchmark function for high churn workload using the Slabbable trait
-fn bench_high_churn_generic<S: Slabbable<usize>>(
-    c: &mut Criterion,
-    name: &str,
-    sizes: &[usize],
-    patterns: &[&str],
-) {
-    let mut group = c.benchmark_group("high_churn_workload");
-    group.sample_size(30); // Increase sample size for better statistical significance
-    
-    for &size in sizes {
-        group.throughput(Throughput::Elements(size as u64));
-        
-        for &pattern in patterns {
-            group.bench_with_input(
-                BenchmarkId::new(format!("{}_{}", name, pattern), size), 
-                &(size, pattern), 
-                |b, (size, pattern)| {
-                    b.iter_with_setup(
-                        || {
-                            // Setup: initialize with capacity and tracking structures
-                            let container = S::new_with_capacity(*size);
-                            let active_keys = Vec::with_capacity(*size);
-                            let removed_keys = Vec::with_capacity(*size / 2);
-                            
-                            (container, active_keys, removed_keys)
-                        },
-                        |(mut container, mut active_keys, mut removed_keys)| {
-                            // First, fill the container halfway
-                            for i in 0..(*size / 2) {
-                                active_keys.push(container.insert(i));
-                            }
-                            
-                            // Now perform high-churn operations in different patterns
-                            let cycles = 20; // More cycles for more realistic workload
-                            
-                            for cycle in 0..cycles {
-                                // Each pattern has a different removal strategy
-                                match *pattern {
-                                    "uniform" => {
-                                        // Remove every third element
-                                        let mut to_remove = Vec::new();
-                                        for i in (0..active_keys.len()).step_by(3) {
-                                            if i < active_keys.len() {
-                                                to_remove.push(i);
-                                            }
-                                        }
-                                        
-                                        // Remove the elements from the end to avoid index shifting
-                                        for i in to_remove.iter().rev() {
-                                            let key = active_keys.swap_remove(*i);
-                                            if let Some(val) = container.remove(key) {
-                                                removed_keys.push((key, val));
-                                            }
-                                        }
-                                    },
-                                    "clustered" => {
-                                        // Remove elements in clusters (25% of elements from a continuous section)
-                                        if !active_keys.is_empty() {
-                                            let cluster_size = active_keys.len() / 4;
-                                            if cluster_size > 0 && active_keys.len() > cluster_size {
-                                                let start = (cycle * 17) % (active_keys.len() - cluster_size);
-                                                
-                                                for _ in 0..cluster_size {
-                                                    let key = active_keys.swap_remove(start);
-                                                    if let Some(val) = container.remove(key) {
-                                                        removed_keys.push((key, val));
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    },
-                                    "random" => {
-                                        // Remove random elements (using a deterministic algorithm)
-                                        let num_to_remove = active_keys.len() / 3;
-                                        for _ in 0..num_to_remove {
-                                            if !active_keys.is_empty() {
-                                                let idx = (cycle * 31) % active_keys.len();
-                                                let key = active_keys.swap_remove(idx);
-                                                if let Some(val) = container.remove(key) {
-                                                    removed_keys.push((key, val));
-                                                }
-                                            }
-                                        }
-                                    },
-                                    _ => unreachable!()
-                                }
-                                
-                                // Reinsert some elements from the removed set
-                                let num_to_reinsert = removed_keys.len() / 2;
-                                for _ in 0..num_to_reinsert {
-                                    if !removed_keys.is_empty() {
-                                        let (key, val) = removed_keys.pop().unwrap();
-                                        // Alternate between inserting at specific index and adding new
-                                        if cycle % 2 == 0 && !container.contains(key) {
-                                            // Insert at the specific index if available
-                                            if container.insert_at(key, val + 1000).is_none() {
-                                                active_keys.push(key);
-                                            }
-                                        } else {
-                                            // Insert new element, getting a new key
-                                            active_keys.push(container.insert(val + 2000));
-                                        }
-                                    }
-                                }
-                                
-                                // Add some fresh elements
-                                let num_new = (*size / 10).max(1);
-                                for i in 0..num_new {
-                                    active_keys.push(container.insert(i + cycle * 1000));
-                                }
-                                
-                                // Occasionally access elements randomly to simulate real use
-                                let mut sum = 0;
-                                for i in 0..active_keys.len() {
-                                    if i % 5 == (cycle % 5) {
-                                        if let Some(&val) = container.get(active_keys[i]) {
-                                            sum += val;
-                                        }
-                                    }
-                                }
-                                black_box(sum);
-                            }
-                            
-                            // Return the final structure for verification
-                            black_box(container)
-                        }
-                    )
-                }
-            );
-        }
+/// Benchmarks `case` at `size` on every backend, warning if the backends
+/// did different logical work
+fn bench_case(group: &mut BenchmarkGroup<'_, WallTime>, group_name: &str, case: &Case, size: usize) {
+    let parameter = &case.parameter(size);
+    let fingerprints: Vec<_> = BACKENDS
+        .into_iter()
+        .map(|backend| {
+            let fingerprint = audit::fingerprint(&Builtin, case, backend, size);
+            (backend, fingerprint.expect("scenario payloads and BACKENDS are all visitable"))
+        })
+        .collect();
+    for divergence in audit::divergences(&fingerprints) {
+        eprintln!("warning: {}/{}/{}: {}", group_name, case.id("*"), parameter, divergence);
+    }
+
+    for (backend, fingerprint) in fingerprints {
+        let visitor = BenchCase {
+            group: &mut *group,
+            group_name,
+            case,
+            backend,
+            parameter,
+            size,
+            ops: fingerprint.ops.total(),
+        };
+        visit_payload(case.payload, visitor)
+            .flatten()
+            .expect("scenario payloads and BACKENDS are all visitable");
     }
-    
-    group.finish();
 }
 
-fn bench_high_churn_workload(c: &mut Criterion) {
-    // Define the test parameters once to ensure identical test conditions
-    let sizes = [1_000, 5_000, 10_000, 50_000];
-    let patterns = ["uniform", "clustered", "random"];
-    
-    // Run the same benchmark with both implementations
-    bench_high_churn_generic::<SlabWrapper<usize>>(c, "slab", &sizes, &patterns);
-    bench_high_churn_generic::<StableVecWrapper<usize>>(c, "stable_vec", &sizes, &patterns);
+/// Seeds overriding every scenario's own, from a comma-separated
+/// `SLABBENCH_SEEDS` environment variable, to sweep several seeds
+fn seed_override() -> Option<Vec<u64>> {
+    let seeds = std::env::var("SLABBENCH_SEEDS").ok()?;
+    Some(
+        seeds
+            .split(',')
+            .map(|seed| seed.trim().parse().expect("SLABBENCH_SEEDS must be a comma-separated list of integers"))
+            .collect(),
+    )
 }
 
-fn bench_sparse_access_workload(c: &mut Criterion) {
-    let mut group = c.benchmark_group("sparse_access_workload");
-    group.sample_size(20); // Reduce sample size for faster benchmarks
-    
-    for size in [1_000, 10_000].iter() {
-        group.throughput(Throughput::Elements(*size as u64));
-        
-        group.bench_with_input(BenchmarkId::new("slab", size), size, |b, size| {
-            b.iter_with_setup(
-                || {
-                    // Setup: create a sparse data structure by removing most elements
-                    let mut slab = Slab::with_capacity(*size);
-                    let mut keys = Vec::with_capacity(*size);
-                    
-                    for i in 0..*size {
-                        keys.push(slab.insert(i));
-                    }
-                    
-                    // Remove 90% of elements, keeping only every 10th
-                    for i in 0..keys.len() {
-                        if i % 10 != 0 {
-                            slab.remove(keys[i]);
-                        }
-                    }
-                    
-                    (slab, keys)
-                },
-                |(mut slab, keys)| {
-                    // Benchmark sparse access patterns
-                    
-                    // Random accesses across the sparse structure
-                    let mut sum = 0;
-                    for &key in &keys {
-                        if let Some(&val) = slab.get(key) {
-                            sum += val;
-                        }
-                    }
-                    black_box(sum);
-                    
-                    // Iteration through sparse structure
-                    sum = 0;
-                    for (_, &val) in slab.iter() {
-                        sum += val;
-                    }
-                    black_box(sum);
-                    
-                    // Insert some new elements in random vacant slots
-                    for i in 0..(*size / 10) {
-                        slab.insert(i * 100);
-                    }
-                }
-            )
-        });
-        
-        group.bench_with_input(BenchmarkId::new("stable_vec", size), size, |b, size| {
-            b.iter_with_setup(
-                || {
-                    // Setup: create a sparse data structure by removing most elements
-                    let mut sv = StableVec::with_capacity(*size);
-                    let mut keys = Vec::with_capacity(*size);
-                    
-                    for i in 0..*size {
-                        keys.push(sv.push(i));
-                    }
-                    
-                    // Remove 90% of elements, keeping only every 10th
-                    for i in 0..keys.len() {
-                        if i % 10 != 0 {
-                            sv.remove(keys[i]);
-                        }
-                    }
-                    
-                    (sv, keys)
-                },
-                |(mut sv, keys)| {
-                    // Benchmark sparse access patterns
-                    
-                    // Random accesses across the sparse structure
-                    let mut sum = 0;
-                    for &key in &keys {
-                        if let Some(&val) = sv.get(key) {
-                            sum += val;
-                        }
-                    }
-                    black_box(sum);
-                    
-                    // Iteration through sparse structure
-                    sum = 0;
-                    for (_, &val) in sv.iter() {
-                        sum += val;
-                    }
-                    black_box(sum);
-                    
-                    // Insert some new elements in random vacant slots
-                    for i in 0..(*size / 10) {
-                        sv.push(i * 100);
-                    }
-                }
-            )
-        });
+/// Runs every scenario file, from `SLABBENCH_SCENARIOS` if set or else the
+/// checked-in `scenarios` directory
+fn bench_scenarios(c: &mut Criterion) {
+    let dir = std::env::var_os("SLABBENCH_SCENARIOS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(scenario::DEFAULT_DIR));
+    let scenarios = Scenario::load_dir(&dir)
+        .unwrap_or_else(|e| panic!("failed to load scenarios from {}: {}", dir.display(), e));
+    let seeds = seed_override();
+
+    for mut scenario in scenarios {
+        if let Some(seeds) = &seeds {
+            scenario.seeds = seeds.clone();
+        }
+
+        let mut group = c.benchmark_group(&scenario.name);
+        group.sample_size(scenario.sample_size);
+
+        for &size in &scenario.sizes {
+            for case in scenario.cases(size) {
+                bench_case(&mut group, &scenario.name, &case, size);
+            }
+        }
+
+        group.finish();
     }
-    
-    group.finish();
 }
 
-fn bench_compaction_workload(c: &mut Criterion) {
-    let mut group = c.benchmark_group("compaction_workload");
-    group.sample_size(20); // Reduce sample size for faster benchmarks
-    
-    for size in [1_000, 10_000].iter() {
-        group.throughput(Throughput::Elements(*size as u64));
-        
-        group.bench_with_input(BenchmarkId::new("slab", size), size, |b, size| {
-            b.iter_with_setup(
-                || {
-                    // Setup: create a fragmented data structure
-                    let mut slab = Slab::with_capacity(*size);
-                    let mut keys = Vec::with_capacity(*size);
-                    
-                    for i in 0..*size {
-                        keys.push(slab.insert(i));
-                    }
-                    
-                    // Remove elements with odd indices to create fragmentation
-                    for i in (1..keys.len()).step_by(2) {
-                        slab.remove(keys[i]);
-                    }
-                    
-                    slab
-                },
-                |mut slab| {
-                    // Benchmark the compaction and operations after compaction
-                    slab.shrink_to_fit();
-                    
-                    // Operations after compaction
-                    let mut sum = 0;
-                    for (_, &val) in slab.iter() {
-                        sum += val;
-                    }
-                    black_box(sum);
-                    
-                    // Add some more elements after compaction
-                    for i in 0..100 {
-                        slab.insert(i * 200);
-                    }
-                }
-            )
-        });
-        
-        group.bench_with_input(BenchmarkId::new("stable_vec", size), size, |b, size| {
-            b.iter_with_setup(
-                || {
-                    // Setup: create a fragmented data structure
-                    let mut sv = StableVec::with_capacity(*size);
-                    let mut keys = Vec::with_capacity(*size);
-                    
-                    for i in 0..*size {
-                        keys.push(sv.push(i));
-                    }
-                    
-                    // Remove elements with odd indices to create fragmentation
-                    for i in (1..keys.len()).step_by(2) {
-                        sv.remove(keys[i]);
-                    }
-                    
-                    sv
-                },
-                |mut sv| {
-                    // Benchmark the compaction and operations after compaction
-                    sv.shrink_to_fit();
-                    
-                    // Operations after compaction
-                    let mut sum = 0;
-                    for (_, &val) in sv.iter() {
-                        sum += val;
-                    }
-                    black_box(sum);
-                    
-                    // Add some more elements after compaction
-                    for i in 0..100 {
-                        sv.push(i * 200);
-                    }
-                }
-            )
-        });
+fn bench_trace_replay(c: &mut Criterion) {
+    // Traces are opt-in: list them in SLABBENCH_TRACES, separated like PATH
+    let Some(paths) = std::env::var_os("SLABBENCH_TRACES") else {
+        return;
+    };
+
+    let mut group = c.benchmark_group("trace_replay");
+    group.sample_size(20);
+
+    for path in std::env::split_paths(&paths) {
+        let trace = Trace::load(&path)
+            .unwrap_or_else(|e| panic!("failed to load trace {}: {}", path.display(), e));
+        let trace_name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+
+        let case = Case {
+            label: String::new(),
+            parameter: Some(trace_name.into_owned()),
+            payload: "usize",
+            seed: 0,
+            workload: Workload::Replay(Arc::new(trace)),
+        };
+        bench_case(&mut group, "trace_replay", &case, 0);
     }
-    
+
     group.finish();
 }
 
-criterion_group!(
-    benches,
-    bench_standard_mixed_workload,
-    bench_high_churn_workload,
-    bench_sparse_access_workload,
-    bench_compaction_workload
-);
-criterion_main!(benches);
\ No newline at end of file
+/// Records the environment of this run, which started at `started`, next
+/// to Criterion's results in `environment.json`, and appends the estimates
+/// it wrote to the benchmark history
+fn record_run(started: SystemTime) {
+    let record = match Record::capture(results::criterion_dir(), started) {
+        Ok(record) if record.estimates.is_empty() => return,
+        Ok(record) => record,
+        Err(e) => {
+            eprintln!("failed to read the results of this run: {}", e);
+            return;
+        }
+    };
+
+    let path = results::criterion_dir().join("environment.json");
+    let json = serde_json::to_string_pretty(&record.environment).expect("the environment is serializable");
+    if let Err(e) = std::fs::write(&path, json) {
+        eprintln!("failed to write {}: {}", path.display(), e);
+    }
+    let path = history::history_file();
+    if let Err(e) = history::append(&path, &record) {
+        eprintln!("failed to record history in {}: {}", path.display(), e);
+    }
+}
+
+criterion_group!(benches, bench_scenarios, bench_trace_replay);
+
+/// Pins the benchmarks to the core in `SLABBENCH_PIN`, if it is set
+fn pin() {
+    let Ok(core) = std::env::var("SLABBENCH_PIN") else {
+        return;
+    };
+    let core = core.trim().parse().expect("SLABBENCH_PIN must be a core number");
+    let isolation = isolation::isolate(core).unwrap_or_else(|e| panic!("failed to isolate the benchmarks: {}", e));
+    for warning in isolation.warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+// Like `criterion_main!`, but isolating the run first if requested and
+// recording it afterwards
+fn main() {
+    pin();
+    let started = SystemTime::now();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    record_run(started);
+}
@@ -0,0 +1,79 @@
+//! Records how the crate is being built, so that results can say which
+//! compiler and settings produced them (see `src/environment.rs`).
+//!
+//! Cargo tells build scripts the profile, optimization level and target
+//! features, but not LTO or codegen units. Those come from the same places
+//! Cargo reads them: `CARGO_PROFILE_<NAME>_*` variables, then the profile's
+//! section in the workspace manifest, then Cargo's defaults.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn set(name: &str, value: &str) {
+    println!("cargo::rustc-env={}={}", name, value);
+}
+
+/// The value of `key` in the `[profile.<profile>]` section of `manifest`,
+/// without quotes. Only handles the simple `key = value` lines that profiles
+/// are written with.
+fn profile_setting(manifest: &str, profile: &str, key: &str) -> Option<String> {
+    let header = format!("[profile.{}]", profile);
+    let mut in_section = false;
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.starts_with('[') {
+            in_section = line == header;
+        } else if in_section
+            && let Some((name, value)) = line.split_once('=')
+            && name.trim() == key
+        {
+            return Some(value.trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+fn main() {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    set("SLABBENCH_RUSTC", &version);
+    set("SLABBENCH_TARGET", &env::var("TARGET").unwrap());
+    set("SLABBENCH_TARGET_FEATURES", &env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default());
+    set("SLABBENCH_OPT_LEVEL", &env::var("OPT_LEVEL").unwrap());
+    set(
+        "SLABBENCH_DEBUG_ASSERTIONS",
+        if env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some() { "true" } else { "false" },
+    );
+
+    // PROFILE is only ever "debug" or "release", also for `cargo bench`,
+    // whose profile inherits from release
+    let profile = match env::var("PROFILE").unwrap().as_str() {
+        "debug" => "dev",
+        _ => "release",
+    };
+    set("SLABBENCH_PROFILE", profile);
+
+    let manifest_path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join("../Cargo.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap_or_default();
+    let setting = |key: &str, default: &str| {
+        let variable = format!("CARGO_PROFILE_{}_{}", profile.to_uppercase(), key.to_uppercase().replace('-', "_"));
+        println!("cargo::rerun-if-env-changed={}", variable);
+        env::var(&variable)
+            .ok()
+            .or_else(|| profile_setting(&manifest, profile, key))
+            .unwrap_or_else(|| default.to_string())
+    };
+    set("SLABBENCH_LTO", &setting("lto", "false"));
+    set("SLABBENCH_CODEGEN_UNITS", &setting("codegen-units", if profile == "dev" { "256" } else { "16" }));
+
+    println!("cargo::rerun-if-changed={}", manifest_path.display());
+    println!("cargo::rerun-if-changed=build.rs");
+}
@@ -0,0 +1,135 @@
+//! Heap accounting through a counting global allocator.
+//!
+//! Accounting is opt-in: a binary has to install [`CountingAlloc`] as its
+//! global allocator before [`measure`] reports anything but zeros.
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: slabbench::alloc::CountingAlloc = slabbench::alloc::CountingAlloc;
+//! ```
+//!
+//! The counters are process-wide, so measurements are only meaningful while a
+//! single thread is allocating.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// A [`GlobalAlloc`] that forwards to [`System`] and counts what passes through.
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    #[inline(always)]
+    fn grow(size: usize) {
+        let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(live, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    fn shrink(size: usize) {
+        LIVE.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        Self::shrink(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            if new_size >= layout.size() {
+                Self::grow(new_size - layout.size());
+            } else {
+                Self::shrink(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Heap usage of a measured piece of code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Bytes allocated by the code that were still live when it returned.
+    pub live_bytes: usize,
+    /// Highest number of bytes allocated by the code at any one time.
+    pub peak_bytes: usize,
+    /// Number of `alloc` and `alloc_zeroed` calls.
+    pub allocations: usize,
+    /// Number of `realloc` calls.
+    pub reallocations: usize,
+}
+
+impl AllocStats {
+    /// Renders the stats as a flat JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"live_bytes\":{},\"peak_bytes\":{},\"allocations\":{},\"reallocations\":{}}}",
+            self.live_bytes, self.peak_bytes, self.allocations, self.reallocations
+        )
+    }
+}
+
+impl fmt::Display for AllocStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "live {} B, peak {} B, {} allocs, {} reallocs",
+            self.live_bytes, self.peak_bytes, self.allocations, self.reallocations
+        )
+    }
+}
+
+/// Returns `true` if [`CountingAlloc`] is the global allocator.
+pub fn is_installed() -> bool {
+    // Any program that got this far has allocated, e.g. for its arguments
+    ALLOCATIONS.load(Ordering::Relaxed) > 0
+}
+
+/// Runs `f` and reports the heap usage attributable to it.
+///
+/// The result of `f` is dropped only after the measurement, so `live_bytes`
+/// includes whatever it returns, e.g. the collection built by a workload.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, AllocStats) {
+    let live_before = LIVE.load(Ordering::Relaxed);
+    PEAK.store(live_before, Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let reallocations_before = REALLOCATIONS.load(Ordering::Relaxed);
+
+    let result = f();
+
+    let stats = AllocStats {
+        live_bytes: LIVE.load(Ordering::Relaxed).saturating_sub(live_before),
+        peak_bytes: PEAK.load(Ordering::Relaxed).saturating_sub(live_before),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations_before,
+        reallocations: REALLOCATIONS.load(Ordering::Relaxed) - reallocations_before,
+    };
+    (result, stats)
+}
@@ -0,0 +1,132 @@
+//! Checking that every backend did the same logical work.
+//!
+//! The backends differ in more than speed: `StableVec::push` never reuses a
+//! vacant slot, and `insert_at` may be a no-op or land elsewhere depending on
+//! the backend, so the "same" workload can leave collections of different
+//! lengths and contents behind. A comparison between such runs is unfair. A
+//! [`Fingerprint`] summarizes the end state of one run and the operations it
+//! performed, and [`divergences`] reports where backends disagree.
+//!
+//! Capacity and the largest key are part of the fingerprint but are expected
+//! to differ, since they depend on how each backend lays out its slots.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::backend::{BackendSet, BackendVisitor, Slabbable};
+use crate::ops::OpCounts;
+use crate::payload::{visit_payload, Payload, PayloadVisitor};
+use crate::scenario::Case;
+
+/// A summary of the state a workload run left behind and the work it did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Fingerprint {
+    pub len: usize,
+    pub capacity: usize,
+    /// The largest key in use, or `None` if the collection is empty.
+    pub max_key: Option<usize>,
+    /// The wrapping sum of every element's checksum, which does not depend
+    /// on the keys or iteration order.
+    pub checksum: usize,
+    pub ops: OpCounts,
+}
+
+impl Fingerprint {
+    /// Fingerprints `container` after a run that performed `ops`.
+    pub fn of<T: Payload, S: Slabbable<T>>(container: &S, ops: OpCounts) -> Self {
+        Self {
+            len: container.len(),
+            capacity: container.capacity(),
+            max_key: container.keys().max(),
+            checksum: container.values().fold(0, |sum, val| sum.wrapping_add(val.checksum())),
+            ops,
+        }
+    }
+}
+
+/// A named part of a fingerprint and how to read it.
+type Field = (&'static str, fn(&Fingerprint) -> u64);
+
+/// The parts of a fingerprint that reflect logical work, which every backend
+/// should agree on.
+const COMPARED: [Field; 9] = [
+    ("len", |f| f.len as u64),
+    ("checksum", |f| f.checksum as u64),
+    ("insert", |f| f.ops.insert),
+    ("insert_at", |f| f.ops.insert_at),
+    ("remove", |f| f.ops.remove),
+    ("get", |f| f.ops.get),
+    ("iterated", |f| f.ops.iterated),
+    ("shrink", |f| f.ops.shrink),
+    ("clear", |f| f.ops.clear),
+];
+
+/// A part of the fingerprint that differs between backends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Divergence {
+    pub field: &'static str,
+    /// Each backend with its value.
+    pub values: Vec<(&'static str, u64)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} differs:", self.field)?;
+        for (i, (backend, value)) in self.values.iter().enumerate() {
+            write!(f, "{} {}={}", if i == 0 { "" } else { "," }, backend, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the fingerprints of one case on several backends and returns
+/// every logical difference between them.
+pub fn divergences(fingerprints: &[(&'static str, Fingerprint)]) -> Vec<Divergence> {
+    COMPARED
+        .iter()
+        .filter_map(|&(field, get)| {
+            let values: Vec<(&'static str, u64)> =
+                fingerprints.iter().map(|(backend, fingerprint)| (*backend, get(fingerprint))).collect();
+            let first = values.first()?.1;
+            values.iter().any(|&(_, value)| value != first).then_some(Divergence { field, values })
+        })
+        .collect()
+}
+
+struct FingerprintCase<'a, B> {
+    backends: &'a B,
+    case: &'a Case,
+    backend: &'a str,
+    size: usize,
+}
+
+impl<B: BackendSet> PayloadVisitor for FingerprintCase<'_, B> {
+    type Output = Option<Fingerprint>;
+
+    fn visit<T: Payload>(self, _name: &'static str) -> Option<Fingerprint> {
+        let backends = self.backends;
+        backends.visit::<T, _>(self.backend, self)
+    }
+}
+
+impl<T: Payload, B> BackendVisitor<T> for FingerprintCase<'_, B> {
+    type Output = Fingerprint;
+
+    fn visit<S: Slabbable<T>>(self, _name: &'static str) -> Fingerprint {
+        self.case.workload.fingerprint::<T, S>(self.size, self.case.seed)
+    }
+}
+
+/// Runs `case` at `size` once on the backend called `backend` in `backends`
+/// and fingerprints the result, or returns `None` if there is no such backend
+/// or payload.
+pub fn fingerprint(backends: &impl BackendSet, case: &Case, backend: &str, size: usize) -> Option<Fingerprint> {
+    let visitor = FingerprintCase {
+        backends,
+        case,
+        backend,
+        size,
+    };
+    visit_payload(case.payload, visitor).flatten()
+}
@@ -0,0 +1,901 @@
+//! Backends that workloads can be written against.
+//!
+//! The [`Slabbable`] trait abstracts over the operations that both `slab` and
+//! `stable-vec` provide, so a workload can be written once and run against
+//! every backend with identical logic. Besides adapters for the two crates,
+//! this module has standard-library baselines built on `Vec<Option<T>>`,
+//! `HashMap` and `BTreeMap`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use stable_vec::StableVec;
+
+/// Operations shared by all stable-index collections under test.
+pub trait Slabbable<T: Default> {
+    /// Creates an empty collection with room for at least `capacity` elements.
+    fn new_with_capacity(capacity: usize) -> Self
+    where
+        Self: Sized;
+
+    /// Inserts `value` and returns the key it was stored at.
+    fn insert(&mut self, value: T) -> usize;
+
+    /// Returns the key that the next call to `insert` will return.
+    fn vacant_key(&self) -> usize;
+
+    /// Stores `value` at exactly `key`, growing the collection if needed, and
+    /// returns the value previously stored there.
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T>;
+
+    /// Removes and returns the value at `key`, if any.
+    fn remove(&mut self, key: usize) -> Option<T>;
+
+    /// Returns a reference to the value at `key`, if any.
+    fn get(&self, key: usize) -> Option<&T>;
+
+    /// Returns a mutable reference to the value at `key`, if any.
+    fn get_mut(&mut self, key: usize) -> Option<&mut T>;
+
+    /// Returns `true` if a value is stored at `key`.
+    fn contains(&self, key: usize) -> bool;
+
+    /// Returns the number of stored values.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if no values are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of slots available without reallocating.
+    fn capacity(&self) -> usize;
+
+    /// Iterates over all stored `(key, value)` pairs. Slab-like backends yield
+    /// them in key order; the map-based baselines make no such promise.
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a;
+
+    /// Iterates over all stored `(key, value)` pairs with mutable access to
+    /// the values, in the same order as [`iter`](Self::iter).
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a;
+
+    /// Iterates over the keys of all stored values, in the same order as
+    /// [`iter`](Self::iter).
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Iterates over all stored values, in the same order as
+    /// [`iter`](Self::iter).
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Removes all values.
+    fn clear(&mut self);
+
+    /// Releases unused memory without changing any key.
+    fn shrink(&mut self);
+}
+
+/// Defines a [`Slabbable`] adapter for the `Slab` of the crate called
+/// `$krate`, like [`SlabWrapper`]. This lets other revisions of `slab`, added
+/// as dependencies under another name, be benchmarked with the same adapter;
+/// see [`crate::revisions`].
+#[macro_export]
+macro_rules! slab_adapter {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident($krate:ident);) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Clone)]
+        $vis struct $name<T>(pub $krate::Slab<T>);
+
+        impl<T: Default> $crate::backend::Slabbable<T> for $name<T> {
+            #[inline(always)]
+            fn new_with_capacity(capacity: usize) -> Self {
+                Self($krate::Slab::with_capacity(capacity))
+            }
+
+            #[inline(always)]
+            fn insert(&mut self, value: T) -> usize {
+                self.0.insert(value)
+            }
+
+            #[inline(always)]
+            fn vacant_key(&self) -> usize {
+                self.0.vacant_key()
+            }
+
+            /// Slab only ever hands out the head of its vacant list, so to land on an
+            /// arbitrary vacant `key` we pop placeholders off the list until `key` is
+            /// at its head (growing the slab if `key` is past the end), insert there,
+            /// and then remove the placeholders in reverse order. Removal pushes onto
+            /// the head of the list, so this restores the original vacant list minus
+            /// `key`, with any newly grown slots threaded onto it.
+            ///
+            /// This is O(1) when `key` is the most recently vacated slot, which is the
+            /// common case when reinserting, and O(n) in the worst case.
+            #[inline(always)]
+            fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+                if let Some(slot) = self.0.get_mut(key) {
+                    return Some(::core::mem::replace(slot, value));
+                }
+
+                let mut placeholders = Vec::new();
+                while self.0.vacant_key() != key {
+                    placeholders.push(self.0.insert(T::default()));
+                }
+                self.0.insert(value);
+
+                for placeholder in placeholders.into_iter().rev() {
+                    self.0.remove(placeholder);
+                }
+
+                None
+            }
+
+            #[inline(always)]
+            fn remove(&mut self, key: usize) -> Option<T> {
+                self.0.try_remove(key)
+            }
+
+            #[inline(always)]
+            fn get(&self, key: usize) -> Option<&T> {
+                self.0.get(key)
+            }
+
+            #[inline(always)]
+            fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+                self.0.get_mut(key)
+            }
+
+            #[inline(always)]
+            fn contains(&self, key: usize) -> bool {
+                self.0.contains(key)
+            }
+
+            #[inline(always)]
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            #[inline(always)]
+            fn capacity(&self) -> usize {
+                self.0.capacity()
+            }
+
+            #[inline(always)]
+            fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+            where
+                T: 'a,
+            {
+                self.0.iter()
+            }
+
+            #[inline(always)]
+            fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+            where
+                T: 'a,
+            {
+                self.0.iter_mut()
+            }
+
+            #[inline(always)]
+            fn clear(&mut self) {
+                self.0.clear()
+            }
+
+            #[inline(always)]
+            fn shrink(&mut self) {
+                self.0.shrink_to_fit()
+            }
+        }
+    };
+}
+
+/// Defines a [`Slabbable`] adapter for the `StableVec` of the crate called
+/// `$krate`, like [`StableVecWrapper`], or for another of its stable vector
+/// types if given as `$krate::$ty`, like [`InlineStableVecWrapper`].
+#[macro_export]
+macro_rules! stable_vec_adapter {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident($krate:ident);) => {
+        $crate::stable_vec_adapter! {
+            $(#[$attr])*
+            $vis struct $name($krate::StableVec);
+        }
+    };
+    ($(#[$attr:meta])* $vis:vis struct $name:ident($krate:ident::$ty:ident);) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Clone)]
+        $vis struct $name<T>(pub $krate::$ty<T>);
+
+        impl<T: Default> $crate::backend::Slabbable<T> for $name<T> {
+            #[inline(always)]
+            fn new_with_capacity(capacity: usize) -> Self {
+                Self($krate::$ty::with_capacity(capacity))
+            }
+
+            #[inline(always)]
+            fn insert(&mut self, value: T) -> usize {
+                self.0.push(value)
+            }
+
+            #[inline(always)]
+            fn vacant_key(&self) -> usize {
+                self.0.next_push_index()
+            }
+
+            #[inline(always)]
+            fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+                if key >= self.0.capacity() {
+                    self.0.reserve_for(key);
+                }
+                self.0.insert(key, value)
+            }
+
+            #[inline(always)]
+            fn remove(&mut self, key: usize) -> Option<T> {
+                // `StableVec::remove` panics on out-of-bounds keys rather than returning `None`
+                if key >= self.0.capacity() {
+                    return None;
+                }
+                self.0.remove(key)
+            }
+
+            #[inline(always)]
+            fn get(&self, key: usize) -> Option<&T> {
+                self.0.get(key)
+            }
+
+            #[inline(always)]
+            fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+                self.0.get_mut(key)
+            }
+
+            #[inline(always)]
+            fn contains(&self, key: usize) -> bool {
+                self.0.has_element_at(key)
+            }
+
+            #[inline(always)]
+            fn len(&self) -> usize {
+                self.0.num_elements()
+            }
+
+            #[inline(always)]
+            fn capacity(&self) -> usize {
+                self.0.capacity()
+            }
+
+            #[inline(always)]
+            fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+            where
+                T: 'a,
+            {
+                self.0.iter()
+            }
+
+            #[inline(always)]
+            fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+            where
+                T: 'a,
+            {
+                self.0.iter_mut()
+            }
+
+            #[inline(always)]
+            fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+            where
+                T: 'a,
+            {
+                self.0.indices()
+            }
+
+            #[inline(always)]
+            fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+            where
+                T: 'a,
+            {
+                self.0.values()
+            }
+
+            #[inline(always)]
+            fn clear(&mut self) {
+                self.0.clear()
+            }
+
+            #[inline(always)]
+            fn shrink(&mut self) {
+                self.0.shrink_to_fit()
+            }
+        }
+    };
+}
+
+slab_adapter! {
+    /// [`Slabbable`] adapter for [`slab::Slab`].
+    pub struct SlabWrapper(slab);
+}
+
+stable_vec_adapter! {
+    /// [`Slabbable`] adapter for [`stable_vec::StableVec`].
+    pub struct StableVecWrapper(stable_vec);
+}
+
+stable_vec_adapter! {
+    /// [`Slabbable`] adapter for [`stable_vec::InlineStableVec`], which stores
+    /// `Option<T>` slots inline instead of tracking occupancy in a bitvector.
+    pub struct InlineStableVecWrapper(stable_vec::InlineStableVec);
+}
+
+/// [`Slabbable`] adapter for [`stable_vec::StableVec`] whose `insert` reuses
+/// vacant slots like `Slab::insert` does, instead of always appending.
+///
+/// Vacant slots are found by searching the occupancy bitvector, starting from
+/// a hint that is kept at or below the lowest vacant slot.
+#[derive(Debug, Default, Clone)]
+pub struct ReusingStableVecWrapper<T> {
+    pub inner: StableVec<T>,
+    hint: usize,
+}
+
+impl<T> ReusingStableVecWrapper<T> {
+    /// Returns the lowest vacant slot, or the next push index if there is none.
+    #[inline(always)]
+    pub fn lowest_vacant_key(&self) -> usize {
+        let end = self.inner.next_push_index();
+        match self.inner.first_empty_slot_from(self.hint) {
+            Some(key) if key < end => key,
+            _ => end,
+        }
+    }
+
+    /// Stores `value` in the lowest vacant slot, appending only if there is none.
+    #[inline(always)]
+    pub fn insert_reusing(&mut self, value: T) -> usize {
+        let key = self.lowest_vacant_key();
+        if key < self.inner.next_push_index() {
+            self.inner.insert(key, value);
+        } else {
+            self.inner.push(value);
+        }
+        self.hint = key + 1;
+        key
+    }
+}
+
+impl<T: Default> Slabbable<T> for ReusingStableVecWrapper<T> {
+    #[inline(always)]
+    fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: StableVec::with_capacity(capacity),
+            hint: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        self.insert_reusing(value)
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.lowest_vacant_key()
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        if key >= self.inner.capacity() {
+            self.inner.reserve_for(key);
+        }
+        self.inner.insert(key, value)
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        // `StableVec::remove` panics on out-of-bounds keys rather than returning `None`
+        if key >= self.inner.capacity() {
+            return None;
+        }
+        let value = self.inner.remove(key);
+        if value.is_some() {
+            self.hint = self.hint.min(key);
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        self.inner.get(key)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.inner.get_mut(key)
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        self.inner.has_element_at(key)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.inner.num_elements()
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        self.inner.iter()
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        self.inner.iter_mut()
+    }
+
+    #[inline(always)]
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        self.inner.indices()
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.inner.values()
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.inner.clear();
+        self.hint = 0;
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {
+        self.inner.shrink_to_fit()
+    }
+}
+
+/// Baseline [`Slabbable`] backend: a `Vec<Option<T>>` with a stack of free
+/// indices, the way a slab is usually written by hand.
+#[derive(Debug, Default, Clone)]
+pub struct VecOptionWrapper<T> {
+    slots: Vec<Option<T>>,
+    /// Vacated indices, most recent last. May contain stale entries for slots
+    /// that were since filled by `insert_at` or truncated by `shrink`.
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T: Default> Slabbable<T> for VecOptionWrapper<T> {
+    #[inline(always)]
+    fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        self.len += 1;
+        while let Some(key) = self.free.pop() {
+            if let Some(slot @ None) = self.slots.get_mut(key) {
+                *slot = Some(value);
+                return key;
+            }
+        }
+        self.slots.push(Some(value));
+        self.slots.len() - 1
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.free
+            .iter()
+            .rev()
+            .copied()
+            .find(|&key| matches!(self.slots.get(key), Some(None)))
+            .unwrap_or(self.slots.len())
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        if key >= self.slots.len() {
+            self.free.extend(self.slots.len()..key);
+            self.slots.resize_with(key + 1, || None);
+        }
+        let old_value = self.slots[key].replace(value);
+        if old_value.is_none() {
+            self.len += 1;
+        }
+        old_value
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        let value = self.slots.get_mut(key)?.take()?;
+        self.free.push(key);
+        self.len -= 1;
+        Some(value)
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        self.slots.get(key)?.as_ref()
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.slots.get_mut(key)?.as_mut()
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        matches!(self.slots.get(key), Some(Some(_)))
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(key, slot)| slot.as_ref().map(|value| (key, value)))
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(key, slot)| slot.as_mut().map(|value| (key, value)))
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.slots.iter().flatten()
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+        self.len = 0;
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+        let end = self.slots.len();
+        self.free.retain(|&key| key < end);
+        self.slots.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+}
+
+/// Baseline [`Slabbable`] backend: a `HashMap` keyed by a counter.
+///
+/// Keys are never reused, and `iter` yields elements in arbitrary order.
+#[derive(Debug, Default, Clone)]
+pub struct HashMapWrapper<T> {
+    map: HashMap<usize, T>,
+    next: usize,
+}
+
+impl<T: Default> Slabbable<T> for HashMapWrapper<T> {
+    #[inline(always)]
+    fn new_with_capacity(capacity: usize) -> Self {
+        Self {
+            map: HashMap::with_capacity(capacity),
+            next: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        let key = self.next;
+        self.next += 1;
+        self.map.insert(key, value);
+        key
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.next
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        self.next = self.next.max(key + 1);
+        self.map.insert(key, value)
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        self.map.remove(&key)
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        self.map.get(&key)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.map.get_mut(&key)
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        self.map.contains_key(&key)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        self.map.iter().map(|(&key, value)| (key, value))
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        self.map.iter_mut().map(|(&key, value)| (key, value))
+    }
+
+    #[inline(always)]
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        self.map.keys().copied()
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.map.values()
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {
+        self.map.shrink_to_fit()
+    }
+}
+
+/// Baseline [`Slabbable`] backend: a `BTreeMap` keyed by a counter.
+///
+/// Keys are never reused. A `BTreeMap` has no notion of spare capacity, so
+/// `capacity` reports the number of elements.
+#[derive(Debug, Default, Clone)]
+pub struct BTreeMapWrapper<T> {
+    map: BTreeMap<usize, T>,
+    next: usize,
+}
+
+impl<T: Default> Slabbable<T> for BTreeMapWrapper<T> {
+    #[inline(always)]
+    fn new_with_capacity(_capacity: usize) -> Self {
+        Self {
+            map: BTreeMap::new(),
+            next: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        let key = self.next;
+        self.next += 1;
+        self.map.insert(key, value);
+        key
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.next
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        self.next = self.next.max(key + 1);
+        self.map.insert(key, value)
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        self.map.remove(&key)
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        self.map.get(&key)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.map.get_mut(&key)
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        self.map.contains_key(&key)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        self.map.iter().map(|(&key, value)| (key, value))
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        self.map.iter_mut().map(|(&key, value)| (key, value))
+    }
+
+    #[inline(always)]
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        self.map.keys().copied()
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        self.map.values()
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {}
+}
+
+/// Names of every backend, in the order they are benchmarked.
+pub const BACKENDS: [&str; 7] = [
+    "slab",
+    "stable_vec",
+    "stable_vec_reuse",
+    "inline_stable_vec",
+    "vec_option",
+    "hash_map",
+    "btree_map",
+];
+
+/// Code that is generic over the backend type, for running it on a backend
+/// chosen by name at runtime with [`visit_backend`].
+pub trait BackendVisitor<T: Default> {
+    type Output;
+
+    fn visit<S: Slabbable<T>>(self, name: &'static str) -> Self::Output;
+}
+
+/// Calls `visitor` with the backend called `name`, or returns `None` if there
+/// is no such backend.
+pub fn visit_backend<T: Default, V: BackendVisitor<T>>(name: &str, visitor: V) -> Option<V::Output> {
+    let output = match name {
+        "slab" => visitor.visit::<SlabWrapper<T>>("slab"),
+        "stable_vec" => visitor.visit::<StableVecWrapper<T>>("stable_vec"),
+        "stable_vec_reuse" => visitor.visit::<ReusingStableVecWrapper<T>>("stable_vec_reuse"),
+        "inline_stable_vec" => visitor.visit::<InlineStableVecWrapper<T>>("inline_stable_vec"),
+        "vec_option" => visitor.visit::<VecOptionWrapper<T>>("vec_option"),
+        "hash_map" => visitor.visit::<HashMapWrapper<T>>("hash_map"),
+        "btree_map" => visitor.visit::<BTreeMapWrapper<T>>("btree_map"),
+        _ => return None,
+    };
+    Some(output)
+}
+
+/// A set of backends that can be chosen by name at runtime. [`Builtin`] is
+/// every backend in [`BACKENDS`]; a harness built against more backends,
+/// such as other revisions of `slab`, implements its own.
+pub trait BackendSet {
+    /// Names of every backend in the set, in the order they are benchmarked.
+    fn names(&self) -> Vec<&'static str>;
+
+    /// Calls `visitor` with the backend called `name`, or returns `None` if
+    /// there is no such backend in the set.
+    fn visit<T: Default, V: BackendVisitor<T>>(&self, name: &str, visitor: V) -> Option<V::Output>;
+}
+
+/// The backends in [`BACKENDS`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Builtin;
+
+impl BackendSet for Builtin {
+    fn names(&self) -> Vec<&'static str> {
+        BACKENDS.to_vec()
+    }
+
+    fn visit<T: Default, V: BackendVisitor<T>>(&self, name: &str, visitor: V) -> Option<V::Output> {
+        visit_backend(name, visitor)
+    }
+}
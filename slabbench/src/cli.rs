@@ -0,0 +1,481 @@
+//! The `slabbench` command line, for running workloads without the Criterion
+//! harness (e.g. on build servers or under `perf` and `heaptrack`) and for
+//! working with saved results.
+//!
+//! The commands are generic over a [`BackendSet`], so that a harness built
+//! against more backends than the builtin ones, such as the one generated by
+//! [`crate::revisions`], offers the same commands for all of them.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use crate::audit::{self, Fingerprint};
+use crate::backend::BackendSet;
+use crate::compare;
+use crate::environment::Environment;
+use crate::history::{self, Trend};
+use crate::isolation;
+use crate::payload::PAYLOADS;
+use crate::results::{self, Results};
+use crate::run::{self, LatencyReport, Measurement};
+use crate::scenario::{self, Scenario};
+
+/// Arguments choosing which scenario cases to run, shared by `run` and
+/// `audit`.
+fn selection_args(backends: &impl BackendSet) -> [Arg; 5] {
+    [
+        Arg::new("scenario")
+            .long("scenario")
+            .short('w')
+            .help("Scenario to run; may be repeated [default: all]")
+            .action(ArgAction::Append),
+        Arg::new("backend")
+            .long("backend")
+            .short('b')
+            .help("Backend to run on; may be repeated [default: all]")
+            .action(ArgAction::Append)
+            .value_parser(PossibleValuesParser::new(backends.names())),
+        Arg::new("payload")
+            .long("payload")
+            .short('p')
+            .help("Element type to store; may be repeated [default: the scenario's payloads]")
+            .action(ArgAction::Append)
+            .value_parser(PossibleValuesParser::new(PAYLOADS)),
+        Arg::new("size")
+            .long("size")
+            .short('s')
+            .help("Collection size to run at; may be repeated [default: the scenario's sizes]")
+            .action(ArgAction::Append)
+            .value_parser(value_parser!(usize)),
+        Arg::new("seed")
+            .long("seed")
+            .help("Seed for cases that use a PRNG; may be repeated [default: the scenario's seeds]")
+            .action(ArgAction::Append)
+            .value_parser(value_parser!(u64)),
+    ]
+}
+
+/// The command line for the backends in `backends`. Callers may add their own
+/// subcommands and handle them before passing the rest to [`dispatch`].
+pub fn command(backends: &impl BackendSet) -> Command {
+    Command::new("slabbench")
+        .about("Benchmark stable-index collections outside of Criterion")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("scenarios")
+                .long("scenarios")
+                .help("Directory of scenario files")
+                .global(true)
+                .default_value(scenario::DEFAULT_DIR)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .subcommand(Command::new("list").about("List the available scenarios, payloads and backends"))
+        .subcommand(
+            Command::new("environment")
+                .about("Print the machine and build details that are recorded with results")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .default_value("text")
+                        .value_parser(["text", "json"]),
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run scenarios against backends and print timing statistics")
+                .args(selection_args(backends))
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .short('n')
+                        .help("Number of timed iterations per case and backend")
+                        .default_value("10")
+                        .value_parser(value_parser!(u64).range(1..)),
+                )
+                .arg(
+                    Arg::new("latency")
+                        .long("latency")
+                        .help("Time every operation and print latency percentiles per kind of operation")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("phases")
+                        .long("phases")
+                        .help("Print the mean time of each phase of the workloads instead of the totals")
+                        .conflicts_with("latency")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("pin")
+                        .long("pin")
+                        .value_name("CORE")
+                        .help("Pin to this core and raise the priority to reduce noise (Linux only)")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .default_value("table")
+                        .value_parser(["table", "json"]),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Check that every backend does the same logical work in each scenario")
+                .args(selection_args(backends))
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .short('v')
+                        .help("Print fingerprints of cases where the backends agree too")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("readme")
+                .about("Regenerate the result tables in the README from Criterion's output")
+                .arg(
+                    Arg::new("criterion")
+                        .long("criterion")
+                        .help("Criterion's output directory [default: target/criterion]")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("readme")
+                        .long("readme")
+                        .help("Markdown file whose marked tables to rewrite")
+                        .default_value(results::README)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Fail instead of writing if the tables are out of date")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Compare two saved Criterion baselines and fail if any benchmark regressed")
+                .arg(
+                    Arg::new("baseline")
+                        .help("Name of the baseline to compare against, e.g. main")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("candidate")
+                        .help("Name of the baseline to compare, e.g. new for the latest run")
+                        .default_value("new"),
+                )
+                .arg(
+                    Arg::new("criterion")
+                        .long("criterion")
+                        .help("Criterion's output directory [default: target/criterion]")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .short('t')
+                        .help("Slowdown in percent that counts as a regression")
+                        .default_value("5")
+                        .value_parser(value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("resamples")
+                        .long("resamples")
+                        .help("Number of bootstrap resamples per benchmark")
+                        .default_value("10000")
+                        .value_parser(value_parser!(u64).range(1..)),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Summarize the benchmark history and plot the trend of every benchmark")
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .help("History file [default: $SLABBENCH_HISTORY or target/slabbench-history/history.jsonl]")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .help("Directory to write the charts to [default: target/slabbench-history/trends]")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("filter")
+                        .long("filter")
+                        .short('f')
+                        .help("Only include benchmarks whose name contains this")
+                        .default_value(""),
+                ),
+        )
+}
+
+fn load_scenarios(matches: &ArgMatches) -> io::Result<Vec<Scenario>> {
+    Scenario::load_dir(matches.get_one::<PathBuf>("scenarios").unwrap())
+}
+
+fn list(backends: &impl BackendSet, matches: &ArgMatches) -> io::Result<()> {
+    let scenarios = load_scenarios(matches)?;
+    let width = scenarios.iter().map(|scenario| scenario.name.len()).max().unwrap_or(0);
+    println!("scenarios:");
+    for scenario in &scenarios {
+        println!("  {:width$}  {}", scenario.name, scenario.description, width = width);
+    }
+    println!("payloads:");
+    for payload in PAYLOADS {
+        println!("  {}", payload);
+    }
+    println!("backends:");
+    for backend in backends.names() {
+        println!("  {}", backend);
+    }
+    Ok(())
+}
+
+fn environment(matches: &ArgMatches) -> io::Result<()> {
+    let environment = Environment::capture();
+    let mut stdout = io::stdout().lock();
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => {
+            serde_json::to_writer_pretty(&mut stdout, &environment)?;
+            writeln!(stdout)
+        }
+        _ => environment.write(stdout),
+    }
+}
+
+/// Values of a repeatable argument, or `all` if it was not given.
+fn selected<'a>(matches: &'a ArgMatches, id: &str, all: &[&'a str]) -> Vec<&'a str> {
+    match matches.get_many::<String>(id) {
+        Some(values) => values.map(String::as_str).collect(),
+        None => all.to_vec(),
+    }
+}
+
+/// The scenarios chosen by [`selection_args`], with their payloads, sizes and
+/// seeds overridden as requested.
+fn selected_scenarios(matches: &ArgMatches) -> io::Result<Vec<Scenario>> {
+    let mut scenarios = load_scenarios(matches)?;
+    if let Some(names) = matches.get_many::<String>("scenario") {
+        let mut chosen = Vec::new();
+        for name in names {
+            let Some(index) = scenarios.iter().position(|scenario| &scenario.name == name) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unknown scenario '{}'", name)));
+            };
+            chosen.push(scenarios[index].clone());
+        }
+        scenarios = chosen;
+    }
+
+    for scenario in &mut scenarios {
+        if let Some(payloads) = matches.get_many::<String>("payload") {
+            scenario.payloads = payloads.cloned().collect();
+        }
+        if let Some(sizes) = matches.get_many::<usize>("size") {
+            scenario.sizes = sizes.copied().collect();
+        }
+        if let Some(seeds) = matches.get_many::<u64>("seed") {
+            scenario.seeds = seeds.copied().collect();
+        }
+    }
+    Ok(scenarios)
+}
+
+fn run(backends: &impl BackendSet, matches: &ArgMatches) -> io::Result<()> {
+    let scenarios = selected_scenarios(matches)?;
+    let names = backends.names();
+    let selected_backends = selected(matches, "backend", &names);
+    let iterations = *matches.get_one::<u64>("iterations").unwrap() as usize;
+    let latency = matches.get_flag("latency");
+    if let Some(&core) = matches.get_one::<usize>("pin") {
+        for warning in isolation::isolate(core)?.warnings {
+            eprintln!("warning: {}", warning);
+        }
+    }
+
+    let mut results: Vec<Measurement> = Vec::new();
+    let mut reports: Vec<LatencyReport> = Vec::new();
+    for scenario in scenarios {
+        for &size in &scenario.sizes {
+            for case in scenario.cases(size) {
+                for &backend in &selected_backends {
+                    const VALIDATED: &str = "backend and payload names are validated by clap";
+                    if latency {
+                        let report = run::measure_latency(backends, &scenario.name, &case, backend, size, iterations);
+                        reports.push(report.expect(VALIDATED));
+                    } else {
+                        let measurement = run::measure(backends, &scenario.name, &case, backend, size, iterations);
+                        results.push(measurement.expect(VALIDATED));
+                    }
+                }
+            }
+        }
+    }
+
+    let stdout = io::stdout().lock();
+    match (matches.get_one::<String>("format").map(String::as_str), latency) {
+        (Some("json"), false) => run::write_json(stdout, &Environment::capture(), &results),
+        (Some("json"), true) => run::write_json(stdout, &Environment::capture(), &reports),
+        (_, false) if matches.get_flag("phases") => run::write_phase_table(stdout, &results),
+        (_, false) => run::write_table(stdout, &results),
+        (_, true) => run::write_latency_table(stdout, &reports),
+    }
+}
+
+/// Fingerprints every selected case on every selected backend and reports
+/// where the backends did different logical work. Returns whether they all
+/// agreed.
+fn audit(backends: &impl BackendSet, matches: &ArgMatches) -> io::Result<bool> {
+    let scenarios = selected_scenarios(matches)?;
+    let names = backends.names();
+    let selected_backends = selected(matches, "backend", &names);
+    let verbose = matches.get_flag("verbose");
+
+    let mut agreed = true;
+    for scenario in scenarios {
+        for &size in &scenario.sizes {
+            for case in scenario.cases(size) {
+                let fingerprints: Vec<(&'static str, Fingerprint)> = names
+                    .iter()
+                    .copied()
+                    .filter(|backend| selected_backends.contains(backend))
+                    .map(|backend| {
+                        let fingerprint = audit::fingerprint(backends, &case, backend, size);
+                        (backend, fingerprint.expect("backend and payload names are validated by clap"))
+                    })
+                    .collect();
+                let divergences = audit::divergences(&fingerprints);
+                if divergences.is_empty() && !verbose {
+                    continue;
+                }
+
+                println!("{}/{}/{}:", scenario.name, case.id("*"), case.parameter(size));
+                for (backend, fingerprint) in &fingerprints {
+                    println!(
+                        "  {:<18} len {}, capacity {}, max key {}, checksum {}, {} ops",
+                        backend,
+                        fingerprint.len,
+                        fingerprint.capacity,
+                        fingerprint.max_key.map_or("-".to_string(), |key| key.to_string()),
+                        fingerprint.checksum,
+                        fingerprint.ops.total(),
+                    );
+                }
+                for divergence in &divergences {
+                    println!("  {}", divergence);
+                }
+                agreed &= divergences.is_empty();
+            }
+        }
+    }
+    Ok(agreed)
+}
+
+/// Rewrites the marked tables of the README, or with `--check` only checks
+/// them. Returns false if the check found them out of date.
+fn readme(matches: &ArgMatches) -> io::Result<bool> {
+    let criterion_dir = matches.get_one::<PathBuf>("criterion").cloned().unwrap_or_else(results::criterion_dir);
+    let path = matches.get_one::<PathBuf>("readme").unwrap();
+
+    let results = Results::load(&criterion_dir)?;
+    let document = fs::read_to_string(path)?;
+    let rewritten = results::rewrite_tables(&document, &results)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+    if rewritten == document {
+        return Ok(true);
+    }
+    if matches.get_flag("check") {
+        eprintln!("slabbench: the tables in {} are out of date", path.display());
+        return Ok(false);
+    }
+    fs::write(path, rewritten)?;
+    Ok(true)
+}
+
+/// Compares two saved baselines and prints the report. Returns false if any
+/// benchmark regressed.
+fn compare(matches: &ArgMatches) -> io::Result<bool> {
+    let criterion_dir = matches.get_one::<PathBuf>("criterion").cloned().unwrap_or_else(results::criterion_dir);
+    let baseline = matches.get_one::<String>("baseline").unwrap();
+    let candidate = matches.get_one::<String>("candidate").unwrap();
+    let threshold = matches.get_one::<f64>("threshold").unwrap() / 100.0;
+    let resamples = *matches.get_one::<u64>("resamples").unwrap() as usize;
+
+    let report = compare::compare(&criterion_dir, baseline, candidate, threshold, resamples)?;
+    if report.comparisons.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no benchmarks in {} have both '{}' and '{}' results", criterion_dir.display(), baseline, candidate),
+        ));
+    }
+    report.write(io::stdout().lock())?;
+    Ok(report.regressions().next().is_none())
+}
+
+/// Prints a summary of the benchmark history and writes a trend chart for
+/// every benchmark in it.
+fn history(matches: &ArgMatches) -> io::Result<()> {
+    let path = matches.get_one::<PathBuf>("history").cloned().unwrap_or_else(history::history_file);
+    let out = matches.get_one::<PathBuf>("out").cloned().unwrap_or_else(|| history::history_dir().join("trends"));
+    let filter = matches.get_one::<String>("filter").unwrap();
+
+    let trends: Vec<Trend> = history::trends(&history::load(&path)?, filter);
+    if trends.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no benchmarks matching '{}' in {}", filter, path.display()),
+        ));
+    }
+    history::write_summary(io::stdout().lock(), &trends)?;
+    fs::create_dir_all(&out)?;
+    for trend in &trends {
+        history::plot(trend, &out.join(history::chart_name(&trend.benchmark)))?;
+    }
+    eprintln!("wrote {} charts to {}", trends.len(), out.display());
+    Ok(())
+}
+
+/// Runs the subcommand chosen in `matches`, which must come from
+/// [`command`]. Subcommands that check something return whether the check
+/// passed.
+pub fn dispatch(backends: &impl BackendSet, matches: &ArgMatches) -> io::Result<bool> {
+    match matches.subcommand() {
+        Some(("list", matches)) => list(backends, matches).map(|()| true),
+        Some(("environment", matches)) => environment(matches).map(|()| true),
+        Some(("run", matches)) => run(backends, matches).map(|()| true),
+        Some(("audit", matches)) => audit(backends, matches),
+        Some(("readme", matches)) => readme(matches),
+        Some(("compare", matches)) => compare(matches),
+        Some(("history", matches)) => history(matches).map(|()| true),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+/// The exit code for the result of [`dispatch`], printing any error.
+pub fn exit_code(result: io::Result<bool>) -> ExitCode {
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("slabbench: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parses the command line and runs it on `backends`.
+pub fn main(backends: &impl BackendSet) -> ExitCode {
+    exit_code(dispatch(backends, &command(backends).get_matches()))
+}
@@ -0,0 +1,300 @@
+//! Comparing two saved result sets and detecting regressions.
+//!
+//! Criterion only compares a run against the one before it. To compare, say,
+//! `main` against a branch, or one revision of a backend against another,
+//! save each run as a named baseline (`cargo bench -- --save-baseline main`)
+//! and [`compare`] the two names. Every benchmark present in both is compared
+//! on its mean time per iteration, with a confidence interval for the change
+//! from bootstrapping the samples in each `sample.json`.
+//!
+//! A benchmark counts as regressed only if the whole confidence interval lies
+//! above the threshold, so noise alone does not fail a comparison.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use oorandom::Rand64;
+use serde::Deserialize;
+
+use crate::results::{benchmark_dirs, read_json, BenchmarkId};
+use crate::run::{format_ns, write_aligned};
+
+/// Confidence level of the intervals for the change.
+const CONFIDENCE: f64 = 0.95;
+
+/// Fixed seed, so comparing the same results always gives the same report.
+const SEED: u128 = 0x5eed;
+
+#[derive(Deserialize)]
+struct SavedSample {
+    iters: Vec<f64>,
+    times: Vec<f64>,
+}
+
+/// Loads the time per iteration of every sample of every benchmark below
+/// `dir` that has results saved under `name`.
+pub fn load_samples(dir: impl AsRef<Path>, name: &str) -> io::Result<BTreeMap<BenchmarkId, Vec<f64>>> {
+    let mut samples = BTreeMap::new();
+    for dir in benchmark_dirs(dir, name)? {
+        let saved: SavedSample = read_json(&dir.join(name).join("sample.json"))?;
+        let per_iteration = saved.times.iter().zip(&saved.iters).map(|(time, iters)| time / iters).collect();
+        samples.insert(BenchmarkId::load(&dir, name)?, per_iteration);
+    }
+    Ok(samples)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The relative change in mean time from a baseline to a candidate, e.g.
+/// `0.05` for 5% slower.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    pub point: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Estimates the change in mean from `baseline` to `candidate` samples, with
+/// a percentile bootstrap confidence interval from `resamples` resamples.
+pub fn bootstrap_change(baseline: &[f64], candidate: &[f64], resamples: usize, rng: &mut Rand64) -> Change {
+    assert!(!baseline.is_empty() && !candidate.is_empty(), "both sample sets must be non-empty");
+
+    let mut resample_mean = |samples: &[f64]| {
+        let sum: f64 = (0..samples.len())
+            .map(|_| samples[rng.rand_range(0..samples.len() as u64) as usize])
+            .sum();
+        sum / samples.len() as f64
+    };
+    let mut changes: Vec<f64> = (0..resamples)
+        .map(|_| resample_mean(candidate) / resample_mean(baseline) - 1.0)
+        .collect();
+    changes.sort_by(f64::total_cmp);
+
+    let point = mean(candidate) / mean(baseline) - 1.0;
+    if changes.is_empty() {
+        return Change {
+            point,
+            lower: point,
+            upper: point,
+        };
+    }
+    let percentile = |p: f64| changes[((p * changes.len() as f64) as usize).min(changes.len() - 1)];
+    Change {
+        point,
+        lower: percentile((1.0 - CONFIDENCE) / 2.0),
+        upper: percentile((1.0 + CONFIDENCE) / 2.0),
+    }
+}
+
+/// How a benchmark changed, judged against a threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The whole confidence interval is above the threshold.
+    Regressed,
+    /// The whole confidence interval is below minus the threshold.
+    Improved,
+    Unchanged,
+}
+
+impl Verdict {
+    /// Judges `change` against `threshold`, a fraction such as `0.05`.
+    pub fn of(change: Change, threshold: f64) -> Self {
+        if change.lower > threshold {
+            Verdict::Regressed
+        } else if change.upper < -threshold {
+            Verdict::Improved
+        } else {
+            Verdict::Unchanged
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verdict::Regressed => "regressed",
+            Verdict::Improved => "improved",
+            Verdict::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// The comparison of one benchmark.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub id: BenchmarkId,
+    pub baseline_mean_ns: f64,
+    pub candidate_mean_ns: f64,
+    pub change: Change,
+    pub verdict: Verdict,
+}
+
+/// The comparison of two result sets.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub baseline: String,
+    pub candidate: String,
+    pub threshold: f64,
+    pub comparisons: Vec<Comparison>,
+    /// Benchmarks with results in only one of the sets.
+    pub only_in_baseline: Vec<BenchmarkId>,
+    pub only_in_candidate: Vec<BenchmarkId>,
+}
+
+impl Report {
+    /// The benchmarks that regressed.
+    pub fn regressions(&self) -> impl Iterator<Item = &Comparison> {
+        self.comparisons.iter().filter(|comparison| comparison.verdict == Verdict::Regressed)
+    }
+
+    /// Writes the report as an aligned plain-text table followed by a
+    /// summary, listing regressions last so they are easy to find.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{} -> {} ({} benchmarks, threshold {:.1}%)",
+            self.baseline,
+            self.candidate,
+            self.comparisons.len(),
+            self.threshold * 100.0
+        )?;
+
+        let mut comparisons: Vec<&Comparison> = self.comparisons.iter().collect();
+        comparisons.sort_by(|a, b| a.change.point.total_cmp(&b.change.point));
+        let rows: Vec<[String; 5]> = comparisons
+            .iter()
+            .map(|comparison| {
+                [
+                    comparison.id.to_string(),
+                    format_ns(comparison.baseline_mean_ns),
+                    format_ns(comparison.candidate_mean_ns),
+                    format!(
+                        "{:+.1}% ({:+.1}% to {:+.1}%)",
+                        comparison.change.point * 100.0,
+                        comparison.change.lower * 100.0,
+                        comparison.change.upper * 100.0
+                    ),
+                    comparison.verdict.name().to_string(),
+                ]
+            })
+            .collect();
+        let header = ["benchmark", self.baseline.as_str(), self.candidate.as_str(), "change", "verdict"];
+        write_aligned(&mut writer, &header, &rows, 1)?;
+
+        for id in &self.only_in_baseline {
+            writeln!(writer, "only in {}: {}", self.baseline, id)?;
+        }
+        for id in &self.only_in_candidate {
+            writeln!(writer, "only in {}: {}", self.candidate, id)?;
+        }
+
+        let regressions: Vec<&Comparison> = self.regressions().collect();
+        let improvements = self.comparisons.iter().filter(|c| c.verdict == Verdict::Improved).count();
+        writeln!(
+            writer,
+            "{} regressed, {} improved, {} unchanged",
+            regressions.len(),
+            improvements,
+            self.comparisons.len() - regressions.len() - improvements
+        )?;
+        for regression in regressions {
+            writeln!(
+                writer,
+                "REGRESSION {}: {:+.1}% (at least {:+.1}%)",
+                regression.id,
+                regression.change.point * 100.0,
+                regression.change.lower * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares every benchmark below `dir` saved under both `baseline` and
+/// `candidate`, flagging changes beyond `threshold` (a fraction such as
+/// `0.05`) using `resamples` bootstrap resamples each.
+pub fn compare(
+    dir: impl AsRef<Path>,
+    baseline: &str,
+    candidate: &str,
+    threshold: f64,
+    resamples: usize,
+) -> io::Result<Report> {
+    let dir = dir.as_ref();
+    let baseline_samples = load_samples(dir, baseline)?;
+    let mut candidate_samples = load_samples(dir, candidate)?;
+    let mut rng = Rand64::new(SEED);
+
+    let mut comparisons = Vec::new();
+    let mut only_in_baseline = Vec::new();
+    for (id, baseline_times) in baseline_samples {
+        let Some(candidate_times) = candidate_samples.remove(&id) else {
+            only_in_baseline.push(id);
+            continue;
+        };
+        if baseline_times.is_empty() || candidate_times.is_empty() {
+            continue;
+        }
+        let change = bootstrap_change(&baseline_times, &candidate_times, resamples, &mut rng);
+        comparisons.push(Comparison {
+            id,
+            baseline_mean_ns: mean(&baseline_times),
+            candidate_mean_ns: mean(&candidate_times),
+            change,
+            verdict: Verdict::of(change, threshold),
+        });
+    }
+
+    Ok(Report {
+        baseline: baseline.to_string(),
+        candidate: candidate.to_string(),
+        threshold,
+        comparisons,
+        only_in_baseline,
+        only_in_candidate: candidate_samples.into_keys().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(lower: f64, upper: f64) -> Change {
+        Change {
+            point: (lower + upper) / 2.0,
+            lower,
+            upper,
+        }
+    }
+
+    #[test]
+    fn identical_samples_are_unchanged() {
+        let samples = [100.0, 102.0, 98.0, 101.0, 99.0, 100.0];
+        let change = bootstrap_change(&samples, &samples, 1000, &mut Rand64::new(SEED));
+        assert_eq!(change.point, 0.0);
+        assert!(change.lower <= 0.0 && change.upper >= 0.0, "{:?}", change);
+        assert_eq!(Verdict::of(change, 0.05), Verdict::Unchanged);
+    }
+
+    #[test]
+    fn clear_slowdown_regresses() {
+        let baseline = [100.0, 102.0, 98.0, 101.0, 99.0, 100.0];
+        let candidate = baseline.map(|time| time * 1.5);
+        let change = bootstrap_change(&baseline, &candidate, 1000, &mut Rand64::new(SEED));
+        assert!((change.point - 0.5).abs() < 1e-9, "{:?}", change);
+        assert!(change.lower > 0.4 && change.upper < 0.6, "{:?}", change);
+        assert_eq!(Verdict::of(change, 0.05), Verdict::Regressed);
+
+        let change = bootstrap_change(&candidate, &baseline, 1000, &mut Rand64::new(SEED));
+        assert_eq!(Verdict::of(change, 0.05), Verdict::Improved);
+    }
+
+    #[test]
+    fn verdict_needs_the_whole_interval_past_the_threshold() {
+        assert_eq!(Verdict::of(change(0.06, 0.10), 0.05), Verdict::Regressed);
+        assert_eq!(Verdict::of(change(0.04, 0.10), 0.05), Verdict::Unchanged);
+        assert_eq!(Verdict::of(change(-0.10, -0.06), 0.05), Verdict::Improved);
+        assert_eq!(Verdict::of(change(-0.10, -0.04), 0.05), Verdict::Unchanged);
+    }
+}
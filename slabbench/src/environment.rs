@@ -0,0 +1,189 @@
+//! The machine and build that produced a set of results.
+//!
+//! Timings from different machines, compilers or profiles are not directly
+//! comparable, so every result set carries an [`Environment`]: `cargo bench`
+//! writes it to `environment.json` in Criterion's output directory and into
+//! every history record, and `run --format json` includes it next to the
+//! measurements. The machine details are read from `/proc` and sysfs on
+//! Linux and left out where they are unavailable; the build details are
+//! recorded by the build script when the crate is compiled.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::isolation::{self, Isolation};
+use crate::revisions::{self, WORKSPACE};
+
+/// How this crate was compiled.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Build {
+    /// The output of `rustc --version`.
+    pub rustc: String,
+    pub target: String,
+    /// The target features that were enabled, e.g. `avx2`.
+    pub target_features: Vec<String>,
+    /// The Cargo profile, `dev` or `release` (which `bench` inherits from).
+    pub profile: String,
+    pub opt_level: String,
+    pub lto: String,
+    pub codegen_units: String,
+    pub debug_assertions: bool,
+}
+
+impl Build {
+    /// The settings recorded by the build script.
+    pub fn current() -> Self {
+        Self {
+            rustc: env!("SLABBENCH_RUSTC").to_string(),
+            target: env!("SLABBENCH_TARGET").to_string(),
+            target_features: env!("SLABBENCH_TARGET_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_string)
+                .collect(),
+            profile: env!("SLABBENCH_PROFILE").to_string(),
+            opt_level: env!("SLABBENCH_OPT_LEVEL").to_string(),
+            lto: env!("SLABBENCH_LTO").to_string(),
+            codegen_units: env!("SLABBENCH_CODEGEN_UNITS").to_string(),
+            debug_assertions: env!("SLABBENCH_DEBUG_ASSERTIONS") == "true",
+        }
+    }
+}
+
+/// The commits that were checked out, or `None` where they could not be
+/// determined.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commits {
+    pub workspace: Option<String>,
+    pub slab: Option<String>,
+    pub stable_vec: Option<String>,
+}
+
+impl Commits {
+    /// The commits currently checked out in the workspace and submodules.
+    pub fn current() -> Self {
+        let commit = |dir: &str| {
+            let dir = Path::new(WORKSPACE).join(dir);
+            revisions::check_repository(&dir).ok()?;
+            revisions::git(&dir, &["rev-parse", "--short=10", "HEAD"]).ok()
+        };
+        Self {
+            workspace: commit("."),
+            slab: commit("slab"),
+            stable_vec: commit("stable-vec"),
+        }
+    }
+}
+
+/// The machine and build results were produced on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Environment {
+    pub cpu: Option<String>,
+    /// The highest frequency the CPU can run at, in MHz.
+    pub max_mhz: Option<u64>,
+    /// The cpufreq scaling governors in use, e.g. `performance`, with
+    /// duplicates removed.
+    pub governors: Vec<String>,
+    /// The number of cores available to this process.
+    pub cores: usize,
+    /// The kernel release, e.g. `6.8.0-45-generic`.
+    pub kernel: Option<String>,
+    pub build: Build,
+    pub commits: Commits,
+    /// How the run was isolated from noise, if it was.
+    pub isolation: Option<Isolation>,
+}
+
+impl Environment {
+    /// Gathers the environment of the current process.
+    pub fn capture() -> Self {
+        Self {
+            cpu: cpu_model(),
+            max_mhz: read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq")
+                .and_then(|khz| khz.parse::<u64>().ok())
+                .map(|khz| khz / 1000),
+            governors: governors(),
+            cores: thread::available_parallelism().map_or(1, usize::from),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease").or_else(|| command_output("uname", &["-r"])),
+            build: Build::current(),
+            commits: Commits::current(),
+            isolation: isolation::applied(),
+        }
+    }
+
+    /// Writes the environment as `key: value` lines.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+        writeln!(writer, "cpu: {}", unknown(&self.cpu))?;
+        writeln!(writer, "max frequency: {}", unknown(&self.max_mhz.map(|mhz| format!("{} MHz", mhz))))?;
+        writeln!(
+            writer,
+            "governor: {}",
+            if self.governors.is_empty() { "unknown".to_string() } else { self.governors.join(", ") }
+        )?;
+        writeln!(writer, "cores: {}", self.cores)?;
+        writeln!(writer, "kernel: {}", unknown(&self.kernel))?;
+        writeln!(writer, "rustc: {}", self.build.rustc)?;
+        writeln!(writer, "target: {}", self.build.target)?;
+        writeln!(writer, "target features: {}", self.build.target_features.join(","))?;
+        writeln!(
+            writer,
+            "profile: {} (opt-level {}, lto {}, codegen-units {}, debug assertions {})",
+            self.build.profile,
+            self.build.opt_level,
+            self.build.lto,
+            self.build.codegen_units,
+            if self.build.debug_assertions { "on" } else { "off" }
+        )?;
+        writeln!(writer, "workspace: {}", unknown(&self.commits.workspace))?;
+        writeln!(writer, "slab: {}", unknown(&self.commits.slab))?;
+        writeln!(writer, "stable-vec: {}", unknown(&self.commits.stable_vec))?;
+        match &self.isolation {
+            Some(isolation) => writeln!(
+                writer,
+                "isolation: pinned to core {}, nice {}, {} warnings",
+                isolation.core,
+                isolation.nice.map_or("unchanged".to_string(), |nice| nice.to_string()),
+                isolation.warnings.len()
+            ),
+            None => writeln!(writer, "isolation: none"),
+        }
+    }
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path).ok().map(|contents| contents.trim().to_string())
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The CPU model from `/proc/cpuinfo` on Linux, or from `sysctl` on macOS.
+fn cpu_model() -> Option<String> {
+    if let Some(cpuinfo) = read_trimmed("/proc/cpuinfo") {
+        return cpuinfo
+            .lines()
+            .find_map(|line| line.strip_prefix("model name")?.split_once(':').map(|(_, model)| model.trim().to_string()));
+    }
+    command_output("sysctl", &["-n", "machdep.cpu.brand_string"])
+}
+
+/// The scaling governor of every cpufreq policy, sorted and deduplicated.
+fn governors() -> Vec<String> {
+    let Ok(policies) = fs::read_dir("/sys/devices/system/cpu/cpufreq") else {
+        return Vec::new();
+    };
+    let mut governors: Vec<String> = policies
+        .filter_map(|policy| read_trimmed(policy.ok()?.path().join("scaling_governor")))
+        .collect();
+    governors.sort();
+    governors.dedup();
+    governors
+}
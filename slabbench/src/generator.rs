@@ -0,0 +1,331 @@
+//! Parameterized workloads: an operation mix plus key-selection distributions.
+//!
+//! A [`WorkloadSpec`] describes a workload declaratively, and
+//! [`WorkloadSpec::generate`] turns it into a [`Trace`] that can be replayed
+//! against any backend with [`Trace::replay`]. Keys in a generated trace are
+//! logical: the n-th insert gets key n, and replay maps that to whatever key
+//! the backend hands out.
+//!
+//! Live elements are ranked by age (oldest first), which is what the
+//! [`KeyDistribution`]s select from.
+
+use oorandom::Rand64;
+use serde::{Deserialize, Serialize};
+
+use crate::trace::{Op, Trace};
+
+/// How a live element is picked for a remove or get.
+///
+/// In JSON this is an object tagged by `kind`, e.g.
+/// `{"kind": "zipf", "exponent": 1.1}` or `{"kind": "oldest_first"}`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum KeyDistribution {
+    /// Every live element is equally likely.
+    Uniform,
+    /// Approximately Zipf-distributed over age rank, so the oldest elements
+    /// are the hottest. Larger exponents are more skewed.
+    Zipf { exponent: f64 },
+    /// The oldest `hot_fraction` of live elements receive `hot_probability`
+    /// of all selections; the rest are picked uniformly.
+    Hotspot { hot_fraction: f64, hot_probability: f64 },
+    /// Always the most recently inserted live element (LIFO).
+    MostRecent,
+    /// Always the oldest live element (FIFO).
+    OldestFirst,
+}
+
+impl KeyDistribution {
+    /// Picks an age rank in `0..live`, where 0 is the oldest live element.
+    fn pick(self, rng: &mut Rand64, live: usize) -> usize {
+        debug_assert!(live > 0);
+        let rank = match self {
+            KeyDistribution::Uniform => rng.rand_range(0..live as u64) as usize,
+            KeyDistribution::Zipf { exponent } => {
+                // Inverse CDF of the continuous power law on [1, live + 1)
+                let n = (live + 1) as f64;
+                let u = rng.rand_float();
+                let x = if (exponent - 1.0).abs() < 1e-9 {
+                    n.powf(u)
+                } else {
+                    let a = 1.0 - exponent;
+                    ((n.powf(a) - 1.0) * u + 1.0).powf(1.0 / a)
+                };
+                x as usize - 1
+            }
+            KeyDistribution::Hotspot {
+                hot_fraction,
+                hot_probability,
+            } => {
+                let hot = ((live as f64 * hot_fraction) as usize).clamp(1, live);
+                if hot == live || rng.rand_float() < hot_probability {
+                    rng.rand_range(0..hot as u64) as usize
+                } else {
+                    rng.rand_range(hot as u64..live as u64) as usize
+                }
+            }
+            KeyDistribution::MostRecent => live - 1,
+            KeyDistribution::OldestFirst => 0,
+        };
+        rank.min(live - 1)
+    }
+
+    fn validate(self) -> Result<(), String> {
+        match self {
+            KeyDistribution::Zipf { exponent } if exponent.is_nan() || exponent <= 0.0 => {
+                Err(format!("Zipf exponent must be positive, got {}", exponent))
+            }
+            KeyDistribution::Hotspot {
+                hot_fraction,
+                hot_probability,
+            } if !(0.0..=1.0).contains(&hot_fraction) || !(0.0..=1.0).contains(&hot_probability) => Err(format!(
+                "hotspot fraction and probability must be in [0, 1], got {} and {}",
+                hot_fraction, hot_probability
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A declarative description of a workload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkloadSpec {
+    /// Number of live elements to fill up to before the mix starts, and to
+    /// keep the collection near afterwards.
+    pub target_occupancy: usize,
+    /// Number of operations to generate after the initial fill.
+    pub operations: usize,
+    /// Percentage of operations that insert a new element.
+    pub insert_percent: f64,
+    /// Percentage of operations that remove a live element.
+    pub remove_percent: f64,
+    /// Percentage of operations that look up a live element.
+    pub get_percent: f64,
+    /// Percentage of operations that iterate over the whole collection.
+    pub iterate_percent: f64,
+    /// How removed elements are chosen.
+    pub remove_distribution: KeyDistribution,
+    /// How looked-up elements are chosen.
+    pub get_distribution: KeyDistribution,
+}
+
+/// Fraction that occupancy may drift from the target before the generator
+/// turns inserts into removes or vice versa.
+const OCCUPANCY_SLACK: f64 = 0.1;
+
+impl WorkloadSpec {
+    /// Checks that the percentages add up to 100 and the distributions'
+    /// parameters are in range.
+    pub fn validate(&self) -> Result<(), String> {
+        let percents = [self.insert_percent, self.remove_percent, self.get_percent, self.iterate_percent];
+        if percents.iter().any(|&p| p.is_nan() || p < 0.0) {
+            return Err("operation percentages must be non-negative".to_string());
+        }
+        let total: f64 = percents.iter().sum();
+        if (total - 100.0).abs() > 1e-6 {
+            return Err(format!("operation percentages must add up to 100, got {}", total));
+        }
+        self.remove_distribution.validate()?;
+        self.get_distribution.validate()
+    }
+
+    /// Generates the operation stream for this spec.
+    ///
+    /// The trace starts with `target_occupancy` inserts, followed by
+    /// `operations` operations drawn from the mix. Whenever occupancy drifts
+    /// more than 10% from the target, inserts become removes (or vice versa)
+    /// to steer it back. Removes and gets on an empty collection become inserts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spec is invalid; see [`validate`](Self::validate).
+    pub fn generate(&self, seed: u64) -> Trace {
+        if let Err(e) = self.validate() {
+            panic!("invalid workload spec: {}", e);
+        }
+
+        let mut rng = Rand64::new(seed as u128);
+        let mut live = LiveSet::new(self.target_occupancy + self.operations);
+        let mut trace = Trace::new();
+
+        for _ in 0..self.target_occupancy {
+            trace.push(Op::Insert { key: live.insert() });
+        }
+
+        let high = (self.target_occupancy as f64 * (1.0 + OCCUPANCY_SLACK)).ceil() as usize;
+        let low = (self.target_occupancy as f64 * (1.0 - OCCUPANCY_SLACK)).floor() as usize;
+
+        for _ in 0..self.operations {
+            let roll = rng.rand_float() * 100.0;
+            let op = if roll < self.insert_percent {
+                OpKind::Insert
+            } else if roll < self.insert_percent + self.remove_percent {
+                OpKind::Remove
+            } else if roll < self.insert_percent + self.remove_percent + self.get_percent {
+                OpKind::Get
+            } else {
+                OpKind::Iterate
+            };
+
+            let op = match op {
+                OpKind::Insert if live.len() > high => OpKind::Remove,
+                OpKind::Remove if live.len() < low => OpKind::Insert,
+                OpKind::Remove | OpKind::Get if live.len() == 0 => OpKind::Insert,
+                op => op,
+            };
+
+            let op = match op {
+                OpKind::Insert => Op::Insert { key: live.insert() },
+                OpKind::Remove => {
+                    let rank = self.remove_distribution.pick(&mut rng, live.len());
+                    Op::Remove { key: live.remove(rank) }
+                }
+                OpKind::Get => {
+                    let rank = self.get_distribution.pick(&mut rng, live.len());
+                    Op::Get { key: live.select(rank) }
+                }
+                OpKind::Iterate => Op::Iterate,
+            };
+            trace.push(op);
+        }
+
+        trace
+    }
+}
+
+#[derive(Clone, Copy)]
+enum OpKind {
+    Insert,
+    Remove,
+    Get,
+    Iterate,
+}
+
+/// The set of live logical keys, supporting selection by age rank in
+/// O(log n) via a Fenwick tree over insertion order.
+struct LiveSet {
+    tree: Vec<usize>,
+    next: usize,
+    len: usize,
+}
+
+impl LiveSet {
+    fn new(max_inserts: usize) -> Self {
+        Self {
+            tree: vec![0; max_inserts + 1],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn update(&mut self, key: usize, delta: isize) {
+        let mut i = key + 1;
+        while i < self.tree.len() {
+            self.tree[i] = self.tree[i].wrapping_add_signed(delta);
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn insert(&mut self) -> usize {
+        let key = self.next;
+        self.next += 1;
+        self.len += 1;
+        self.update(key, 1);
+        key
+    }
+
+    /// Returns the live key with the given age rank.
+    fn select(&self, rank: usize) -> usize {
+        let mut remaining = rank + 1;
+        let mut pos = 0;
+        let mut step = (self.tree.len() - 1).next_power_of_two();
+        while step > 0 {
+            let next = pos + step;
+            if next < self.tree.len() && self.tree[next] < remaining {
+                pos = next;
+                remaining -= self.tree[next];
+            }
+            step /= 2;
+        }
+        pos
+    }
+
+    fn remove(&mut self, rank: usize) -> usize {
+        let key = self.select(rank);
+        self.len -= 1;
+        self.update(key, -1);
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zipf_spec() -> WorkloadSpec {
+        WorkloadSpec {
+            target_occupancy: 100,
+            operations: 1000,
+            insert_percent: 30.0,
+            remove_percent: 30.0,
+            get_percent: 35.0,
+            iterate_percent: 5.0,
+            remove_distribution: KeyDistribution::Zipf { exponent: 1.1 },
+            get_distribution: KeyDistribution::Zipf { exponent: 1.0 },
+        }
+    }
+
+    #[test]
+    fn live_set_matches_naive_vec() {
+        const INSERTS: usize = 2000;
+        let mut rng = Rand64::new(7);
+        let mut live = LiveSet::new(INSERTS);
+        // Live keys in insertion order, so the index of a key is its age rank
+        let mut naive: Vec<usize> = Vec::new();
+        let mut inserted = 0;
+
+        while inserted < INSERTS {
+            if naive.is_empty() || rng.rand_range(0..5) < 3 {
+                naive.push(live.insert());
+                inserted += 1;
+            } else {
+                let rank = rng.rand_range(0..naive.len() as u64) as usize;
+                assert_eq!(live.remove(rank), naive.remove(rank));
+            }
+            assert_eq!(live.len(), naive.len());
+            if inserted % 100 == 0 {
+                for (rank, &key) in naive.iter().enumerate() {
+                    assert_eq!(live.select(rank), key, "rank {} of {}", rank, naive.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_seed_is_reproducible() {
+        let spec = zipf_spec();
+        assert_eq!(spec.generate(42), spec.generate(42));
+        assert_ne!(spec.generate(42), spec.generate(43));
+
+        let picks = |seed| {
+            let mut rng = Rand64::new(seed);
+            (0..1000).map(|_| KeyDistribution::Zipf { exponent: 1.1 }.pick(&mut rng, 50)).collect::<Vec<_>>()
+        };
+        assert_eq!(picks(1), picks(1));
+    }
+
+    #[test]
+    fn zipf_favours_the_oldest() {
+        let mut rng = Rand64::new(1);
+        let mut counts = [0usize; 50];
+        for _ in 0..10_000 {
+            counts[KeyDistribution::Zipf { exponent: 1.1 }.pick(&mut rng, counts.len())] += 1;
+        }
+        assert!(counts[0] > counts[1] && counts[1] > counts[10] && counts[10] > counts[49], "{:?}", counts);
+    }
+}
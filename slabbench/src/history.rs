@@ -0,0 +1,223 @@
+//! A local history of benchmark results, for spotting gradual drift.
+//!
+//! Criterion only keeps the latest run and whatever baselines were saved
+//! explicitly, which hides slow drift over months. After every `cargo bench`
+//! the harness appends a [`Record`] of the estimates that run produced,
+//! together with the [`Environment`] it ran in, as one line of JSON to
+//! `target/slabbench-history/history.jsonl`. [`trends`] turns the history
+//! back into one series per benchmark, which [`plot`] draws as an SVG chart.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::environment::Environment;
+use crate::results::{self, benchmark_dirs, BenchmarkId, Estimate};
+use crate::run::{format_ns, write_aligned};
+
+/// The directory the history is kept in.
+pub fn history_dir() -> PathBuf {
+    results::target_dir().join("slabbench-history")
+}
+
+/// The history file, with one [`Record`] per line: `SLABBENCH_HISTORY` if
+/// that is set, or else `history.jsonl` in the [`history_dir`].
+pub fn history_file() -> PathBuf {
+    std::env::var_os("SLABBENCH_HISTORY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| history_dir().join("history.jsonl"))
+}
+
+/// One benchmark run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// When the run finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub environment: Environment,
+    /// Every benchmark the run measured, by `group/function/parameter`.
+    pub estimates: BTreeMap<String, Estimate>,
+}
+
+impl Record {
+    /// Records the benchmarks below the Criterion output directory `dir`
+    /// whose latest results were written at or after `since`, i.e. those
+    /// measured by a run that started then.
+    pub fn capture(dir: impl AsRef<Path>, since: SystemTime) -> io::Result<Self> {
+        let mut estimates = BTreeMap::new();
+        for dir in benchmark_dirs(dir, "new")? {
+            if fs::metadata(dir.join("new").join("estimates.json"))?.modified()? < since {
+                continue;
+            }
+            estimates.insert(BenchmarkId::load(&dir, "new")?.to_string(), Estimate::load(&dir, "new")?);
+        }
+        Ok(Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+            environment: Environment::capture(),
+            estimates,
+        })
+    }
+}
+
+/// Appends `record` to the history file at `path`, creating it if needed.
+pub fn append(path: &Path, record: &Record) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+    line.push('\n');
+    OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+/// Reads every record in the history file at `path`, oldest first.
+pub fn load(path: &Path) -> io::Result<Vec<Record>> {
+    let mut records: Vec<Record> = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), number + 1, e))
+        })?;
+        records.push(record);
+    }
+    records.sort_by_key(|record| record.timestamp);
+    Ok(records)
+}
+
+/// The estimates of one benchmark over time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trend {
+    pub benchmark: String,
+    /// Each run's timestamp and estimate, oldest first.
+    pub points: Vec<(u64, Estimate)>,
+}
+
+impl Trend {
+    /// The relative change in mean from the first run to the latest, e.g.
+    /// `0.05` for 5% slower.
+    pub fn change(&self) -> f64 {
+        let (first, last) = (self.points[0].1, self.points[self.points.len() - 1].1);
+        last.mean_ns / first.mean_ns - 1.0
+    }
+}
+
+/// Splits `records` into one trend per benchmark, keeping only benchmarks
+/// whose name contains `filter`.
+pub fn trends(records: &[Record], filter: &str) -> Vec<Trend> {
+    let mut points: BTreeMap<&str, Vec<(u64, Estimate)>> = BTreeMap::new();
+    for record in records {
+        for (benchmark, estimate) in &record.estimates {
+            if benchmark.contains(filter) {
+                points.entry(benchmark).or_default().push((record.timestamp, *estimate));
+            }
+        }
+    }
+    points
+        .into_iter()
+        .map(|(benchmark, points)| Trend {
+            benchmark: benchmark.to_string(),
+            points,
+        })
+        .collect()
+}
+
+/// Formats seconds since the Unix epoch as a UTC date such as `2025-03-14`.
+pub fn format_date(timestamp: u64) -> String {
+    // Howard Hinnant's days_from_civil, inverted
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Writes the first and latest mean of every trend and the change between
+/// them as an aligned plain-text table.
+pub fn write_summary<W: Write>(writer: W, trends: &[Trend]) -> io::Result<()> {
+    let header = ["benchmark", "runs", "since", "first", "latest", "change"];
+    let rows: Vec<[String; 6]> = trends
+        .iter()
+        .map(|trend| {
+            [
+                trend.benchmark.clone(),
+                trend.points.len().to_string(),
+                format_date(trend.points[0].0),
+                format_ns(trend.points[0].1.mean_ns),
+                format_ns(trend.points[trend.points.len() - 1].1.mean_ns),
+                format!("{:+.1}%", trend.change() * 100.0),
+            ]
+        })
+        .collect();
+    write_aligned(writer, &header, &rows, 1)
+}
+
+/// The file name of the chart for `benchmark`, with every character that is
+/// awkward in file names replaced.
+pub fn chart_name(benchmark: &str) -> String {
+    let name: String = benchmark
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    format!("{}.svg", name)
+}
+
+/// Draws `trend` as an SVG chart at `path`: the mean of every run over time,
+/// with its confidence interval shaded.
+pub fn plot(trend: &Trend, path: &Path) -> io::Result<()> {
+    const DAY: f64 = 86_400.0;
+    let days = |timestamp: u64| timestamp as f64 / DAY;
+
+    let draw = || -> Result<(), Box<dyn std::error::Error>> {
+        let first = days(trend.points[0].0);
+        let last = days(trend.points[trend.points.len() - 1].0);
+        // Leave room around a single run, or runs on the same day
+        let (start, end) = if last - first < 1.0 { (first - 0.5, last + 0.5) } else { (first, last) };
+        let lowest = trend.points.iter().map(|(_, estimate)| estimate.lower_ns).fold(f64::INFINITY, f64::min);
+        let highest = trend.points.iter().map(|(_, estimate)| estimate.upper_ns).fold(0.0, f64::max);
+        let margin = (highest - lowest).max(highest * 0.01) * 0.1;
+
+        let root = SVGBackend::new(path, (960, 480)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(&trend.benchmark, ("sans-serif", 20))
+            .margin(12)
+            .x_label_area_size(40)
+            .y_label_area_size(80)
+            .build_cartesian_2d(start..end, (lowest - margin).max(0.0)..highest + margin)?;
+        chart
+            .configure_mesh()
+            .x_labels(8)
+            .x_label_formatter(&|&day| format_date((day * DAY) as u64))
+            .y_label_formatter(&|&ns| format_ns(ns))
+            .y_desc("mean time per iteration")
+            .draw()?;
+
+        let band: Vec<(f64, f64)> = trend
+            .points
+            .iter()
+            .map(|(timestamp, estimate)| (days(*timestamp), estimate.upper_ns))
+            .chain(trend.points.iter().rev().map(|(timestamp, estimate)| (days(*timestamp), estimate.lower_ns)))
+            .collect();
+        chart.draw_series(std::iter::once(Polygon::new(band, BLUE.mix(0.2).filled())))?;
+        chart.draw_series(
+            LineSeries::new(
+                trend.points.iter().map(|(timestamp, estimate)| (days(*timestamp), estimate.mean_ns)),
+                BLUE.stroke_width(2),
+            )
+            .point_size(3),
+        )?;
+        root.present()?;
+        Ok(())
+    };
+    draw().map_err(|e| io::Error::other(format!("{}: {}", path.display(), e)))
+}
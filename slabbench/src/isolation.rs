@@ -0,0 +1,123 @@
+//! Reducing run-to-run noise on Linux.
+//!
+//! The larger workloads are sensitive to the scheduler moving the benchmark
+//! thread between cores, to other processes preempting it, and to the CPU
+//! changing its clock speed. [`isolate`] pins the calling thread to one core
+//! with `sched_setaffinity`, raises the priority of the process if it is
+//! permitted to, and warns about a frequency governor other than
+//! `performance` or about turbo boost, which both let the clock speed vary
+//! with load and temperature. The conditions are kept for the rest of the
+//! process and recorded in its [`crate::environment::Environment`].
+//!
+//! Isolation is opt-in: `run --pin <CORE>` for the binary and
+//! `SLABBENCH_PIN=<CORE>` for `cargo bench`.
+
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::io;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+/// The conditions a run was isolated under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Isolation {
+    /// The core the benchmark thread was pinned to.
+    pub core: usize,
+    /// The nice value the process was raised to, or `None` if it was not
+    /// permitted to.
+    pub nice: Option<i32>,
+    /// The frequency governor of the core, if known.
+    pub governor: Option<String>,
+    /// Whether turbo boost was enabled, if known.
+    pub turbo: Option<bool>,
+    /// Every condition that is likely to add noise.
+    pub warnings: Vec<String>,
+}
+
+/// The nice value to raise the process to; the highest priority there is.
+#[cfg(target_os = "linux")]
+const NICE: i32 = -20;
+
+static APPLIED: OnceLock<Isolation> = OnceLock::new();
+
+/// The isolation applied to this process, if any.
+pub fn applied() -> Option<Isolation> {
+    APPLIED.get().cloned()
+}
+
+/// Whether turbo boost is enabled, from `intel_pstate` or from the generic
+/// cpufreq `boost` switch.
+#[cfg(target_os = "linux")]
+fn turbo() -> Option<bool> {
+    let read = |path: &str| fs::read_to_string(path).ok().map(|contents| contents.trim() == "1");
+    read("/sys/devices/system/cpu/intel_pstate/no_turbo")
+        .map(|no_turbo| !no_turbo)
+        .or_else(|| read("/sys/devices/system/cpu/cpufreq/boost"))
+}
+
+/// Pins the calling thread to `core`, raises the priority of the process if
+/// permitted, and checks the core's frequency scaling. Fails if the thread
+/// cannot be pinned, e.g. because there is no such core; everything else
+/// only adds warnings. The first isolation of the process is the one that
+/// [`applied`] returns.
+#[cfg(target_os = "linux")]
+pub fn isolate(core: usize) -> io::Result<Isolation> {
+    if core >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("there is no core {}", core)));
+    }
+    // SAFETY: an all-zero cpu_set_t is a valid empty set, and the set outlives
+    // the call that reads it
+    let pinned = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set)
+    };
+    if pinned != 0 {
+        let e = io::Error::last_os_error();
+        return Err(io::Error::new(e.kind(), format!("failed to pin to core {}: {}", core, e)));
+    }
+
+    let mut warnings = Vec::new();
+    // SAFETY: setpriority has no memory-safety requirements
+    let nice = if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE) } == 0 {
+        Some(NICE)
+    } else {
+        warnings.push(format!(
+            "could not raise the priority ({}); run as root or with CAP_SYS_NICE",
+            io::Error::last_os_error()
+        ));
+        None
+    };
+
+    let governor = fs::read_to_string(format!("/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor", core))
+        .ok()
+        .map(|governor| governor.trim().to_string());
+    match &governor {
+        Some(governor) if governor != "performance" => {
+            warnings.push(format!("the frequency governor of core {} is '{}', not 'performance'", core, governor))
+        }
+        Some(_) => {}
+        None => warnings.push(format!("could not read the frequency governor of core {}", core)),
+    }
+    let turbo = turbo();
+    if turbo == Some(true) {
+        warnings.push("turbo boost is enabled".to_string());
+    }
+
+    let isolation = Isolation {
+        core,
+        nice,
+        governor,
+        turbo,
+        warnings,
+    };
+    let _ = APPLIED.set(isolation.clone());
+    Ok(isolation)
+}
+
+/// Pinning is only implemented for Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn isolate(_core: usize) -> io::Result<Isolation> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "pinning to a core is only supported on Linux"))
+}
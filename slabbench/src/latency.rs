@@ -0,0 +1,480 @@
+//! Per-operation latency histograms.
+//!
+//! Criterion times a whole workload, so a rare slow operation (say, a 5 ms
+//! reallocation inside one `insert`) vanishes into the mean. Wrapping a backend
+//! in [`Timed`] instead times every individual operation with a cheap cycle
+//! counter and records it in a log-bucketed [`Histogram`] for its [`OpKind`].
+//!
+//! Histograms are kept per thread, so that workloads can construct the timed
+//! backend themselves. Call [`reset`] after any setup that should not be
+//! counted, and [`take`] once the measured part is done.
+//!
+//! Iteration is timed from the call to `iter` (or `iter_mut`, `keys`,
+//! `values`) until the iterator is dropped, so it includes the work done by
+//! the loop consuming it.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::backend::Slabbable;
+
+/// Kinds of operation that are timed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Insert,
+    InsertAt,
+    Remove,
+    /// `get`, `get_mut` and `contains`.
+    Get,
+    /// A whole pass of `iter`, `iter_mut`, `keys` or `values`.
+    Iterate,
+    Shrink,
+    Clear,
+}
+
+impl OpKind {
+    /// All kinds, in reporting order.
+    pub const ALL: [OpKind; 7] = [
+        OpKind::Insert,
+        OpKind::InsertAt,
+        OpKind::Remove,
+        OpKind::Get,
+        OpKind::Iterate,
+        OpKind::Shrink,
+        OpKind::Clear,
+    ];
+
+    /// The name of this kind, matching the trace text format.
+    pub fn name(self) -> &'static str {
+        match self {
+            OpKind::Insert => "insert",
+            OpKind::InsertAt => "insert_at",
+            OpKind::Remove => "remove",
+            OpKind::Get => "get",
+            OpKind::Iterate => "iter",
+            OpKind::Shrink => "shrink",
+            OpKind::Clear => "clear",
+        }
+    }
+}
+
+/// Reads the cycle counter.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+fn ticks() -> u64 {
+    // SAFETY: RDTSC is available on every x86_64 CPU
+    unsafe { std::arch::x86_64::_rdtsc() }
+}
+
+/// Reads nanoseconds since the first call, where no cycle counter is used.
+#[cfg(not(target_arch = "x86_64"))]
+#[inline(always)]
+fn ticks() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Nanoseconds per tick, calibrated against [`Instant`] on first use.
+fn ns_per_tick() -> f64 {
+    static NS_PER_TICK: OnceLock<f64> = OnceLock::new();
+    *NS_PER_TICK.get_or_init(|| {
+        if cfg!(not(target_arch = "x86_64")) {
+            return 1.0;
+        }
+        let (start, start_ticks) = (Instant::now(), ticks());
+        while start.elapsed() < Duration::from_millis(20) {}
+        let (elapsed, elapsed_ticks) = (start.elapsed(), ticks().wrapping_sub(start_ticks));
+        elapsed.as_nanos() as f64 / elapsed_ticks as f64
+    })
+}
+
+/// Converts a tick count to nanoseconds.
+pub fn ticks_to_ns(ticks: u64) -> u64 {
+    (ticks as f64 * ns_per_tick()).round() as u64
+}
+
+/// Bits of precision below the leading bit; each power of two is split into
+/// `2^SUB_BUCKET_BITS` buckets, so values are kept to within about 3%.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (64 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// An HDR-style histogram of `u64` values with log-linear buckets: values
+/// below 64 are counted exactly, larger ones with 5 significant bits.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+            max: 0,
+        }
+    }
+
+    fn bucket(value: u64) -> usize {
+        let exponent = 63 - (value | 1).leading_zeros();
+        if exponent < SUB_BUCKET_BITS {
+            return value as usize;
+        }
+        let shift = exponent - SUB_BUCKET_BITS;
+        ((shift as usize + 1) << SUB_BUCKET_BITS) + ((value >> shift) as usize & (SUB_BUCKETS - 1))
+    }
+
+    /// The largest value that falls in `bucket`.
+    fn highest_in(bucket: usize) -> u64 {
+        if bucket < 2 * SUB_BUCKETS {
+            return bucket as u64;
+        }
+        let shift = (bucket >> SUB_BUCKET_BITS) - 1;
+        let lowest = ((bucket & (SUB_BUCKETS - 1)) | SUB_BUCKETS) as u64;
+        (lowest << shift) + ((1 << shift) - 1)
+    }
+
+    /// Records one value.
+    #[inline]
+    pub fn record(&mut self, value: u64) {
+        self.buckets[Self::bucket(value)] += 1;
+        self.count += 1;
+        self.max = self.max.max(value);
+    }
+
+    /// Adds every value recorded in `other`.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bucket, &count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The largest recorded value, exactly.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The value at `quantile` (in `[0, 1]`), as the highest value in its
+    /// bucket, or 0 if nothing was recorded.
+    pub fn quantile(&self, quantile: f64) -> u64 {
+        let rank = ((quantile * self.count as f64).ceil() as u64).clamp(1, self.count.max(1));
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank && count > 0 {
+                return Self::highest_in(bucket).min(self.max);
+            }
+        }
+        0
+    }
+}
+
+/// Latency percentiles for one kind of operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpLatency {
+    pub op: &'static str,
+    pub count: u64,
+    pub p50_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+}
+
+/// One [`Histogram`] of tick counts per [`OpKind`].
+#[derive(Debug, Clone, Default)]
+pub struct OpHistograms {
+    histograms: [Histogram; OpKind::ALL.len()],
+}
+
+impl OpHistograms {
+    /// The histogram for `kind`, in ticks.
+    pub fn get(&self, kind: OpKind) -> &Histogram {
+        &self.histograms[kind as usize]
+    }
+
+    #[inline]
+    fn record(&mut self, kind: OpKind, ticks: u64) {
+        self.histograms[kind as usize].record(ticks);
+    }
+
+    /// Adds every value recorded in `other`.
+    pub fn merge(&mut self, other: &OpHistograms) {
+        for (histogram, other) in self.histograms.iter_mut().zip(&other.histograms) {
+            histogram.merge(other);
+        }
+    }
+
+    /// Percentiles in nanoseconds for every kind of operation that was
+    /// recorded at least once.
+    pub fn summaries(&self) -> Vec<OpLatency> {
+        OpKind::ALL
+            .into_iter()
+            .filter(|&kind| self.get(kind).count() > 0)
+            .map(|kind| {
+                let histogram = self.get(kind);
+                OpLatency {
+                    op: kind.name(),
+                    count: histogram.count(),
+                    p50_ns: ticks_to_ns(histogram.quantile(0.5)),
+                    p99_ns: ticks_to_ns(histogram.quantile(0.99)),
+                    p999_ns: ticks_to_ns(histogram.quantile(0.999)),
+                    max_ns: ticks_to_ns(histogram.max()),
+                }
+            })
+            .collect()
+    }
+}
+
+thread_local! {
+    static HISTOGRAMS: RefCell<OpHistograms> = RefCell::new(OpHistograms::default());
+}
+
+/// Discards everything recorded on this thread so far.
+pub fn reset() {
+    HISTOGRAMS.with_borrow_mut(|histograms| *histograms = OpHistograms::default());
+}
+
+/// Returns everything recorded on this thread since the last [`reset`] or
+/// `take`, and starts over.
+pub fn take() -> OpHistograms {
+    HISTOGRAMS.take()
+}
+
+#[inline(always)]
+fn time<R>(kind: OpKind, op: impl FnOnce() -> R) -> R {
+    let start = ticks();
+    let result = op();
+    let elapsed = ticks().wrapping_sub(start);
+    HISTOGRAMS.with_borrow_mut(|histograms| histograms.record(kind, elapsed));
+    result
+}
+
+/// An iterator that records the time from its creation until it is dropped.
+struct TimedIter<I> {
+    inner: I,
+    start: u64,
+}
+
+impl<I> TimedIter<I> {
+    #[inline(always)]
+    fn new(inner: I) -> Self {
+        Self { inner, start: ticks() }
+    }
+}
+
+impl<I: Iterator> Iterator for TimedIter<I> {
+    type Item = I::Item;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<I::Item> {
+        self.inner.next()
+    }
+}
+
+impl<I> Drop for TimedIter<I> {
+    fn drop(&mut self) {
+        let elapsed = ticks().wrapping_sub(self.start);
+        HISTOGRAMS.with_borrow_mut(|histograms| histograms.record(OpKind::Iterate, elapsed));
+    }
+}
+
+/// A [`Slabbable`] wrapper that times every operation into this thread's
+/// histograms. `vacant_key`, `len`, `capacity` and construction are not
+/// timed.
+#[derive(Debug, Default)]
+pub struct Timed<S>(pub S);
+
+impl<T: Default, S: Slabbable<T>> Slabbable<T> for Timed<S> {
+    fn new_with_capacity(capacity: usize) -> Self {
+        Timed(S::new_with_capacity(capacity))
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        time(OpKind::Insert, || self.0.insert(value))
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.0.vacant_key()
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        time(OpKind::InsertAt, || self.0.insert_at(key, value))
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        time(OpKind::Remove, || self.0.remove(key))
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        time(OpKind::Get, || self.0.get(key))
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        time(OpKind::Get, || self.0.get_mut(key))
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        time(OpKind::Get, || self.0.contains(key))
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        TimedIter::new(self.0.iter())
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        TimedIter::new(self.0.iter_mut())
+    }
+
+    #[inline(always)]
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        TimedIter::new(self.0.keys())
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        TimedIter::new(self.0.values())
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        time(OpKind::Clear, || self.0.clear())
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {
+        time(OpKind::Shrink, || self.0.shrink())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `actual` is the top of the bucket holding `exact`, which
+    /// is at most `exact / 32` above it.
+    fn assert_in_bucket(actual: u64, exact: u64) {
+        assert!(exact <= actual && actual <= exact + exact / SUB_BUCKETS as u64, "{} for {}", actual, exact);
+    }
+
+    #[test]
+    fn quantiles_of_known_values() {
+        let mut histogram = Histogram::new();
+        for value in 1..=10_000 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.count(), 10_000);
+        assert_eq!(histogram.max(), 10_000);
+        for quantile in [0.5f64, 0.99, 0.999] {
+            let exact = (quantile * 10_000.0).ceil() as u64;
+            assert_in_bucket(histogram.quantile(quantile), exact);
+        }
+        assert_eq!(histogram.quantile(1.0), 10_000);
+        assert_eq!(Histogram::new().quantile(0.5), 0);
+    }
+
+    #[test]
+    fn small_values_are_exact() {
+        let mut histogram = Histogram::new();
+        for value in 0..64 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.quantile(0.5), 31);
+        assert_eq!(histogram.quantile(0.99), 63);
+    }
+
+    #[test]
+    fn bucket_edges() {
+        // 64 is the first value that shares a bucket, with 65
+        assert_eq!(Histogram::bucket(63) + 1, Histogram::bucket(64));
+        assert_eq!(Histogram::bucket(64), Histogram::bucket(65));
+        assert_eq!(Histogram::bucket(65) + 1, Histogram::bucket(66));
+        assert_eq!(Histogram::highest_in(Histogram::bucket(64)), 65);
+        assert_eq!(Histogram::bucket(u64::MAX), BUCKETS - 1);
+        assert_eq!(Histogram::highest_in(BUCKETS - 1), u64::MAX);
+        for exponent in SUB_BUCKET_BITS + 1..64 {
+            let lowest = 1u64 << exponent;
+            assert_eq!(Histogram::highest_in(Histogram::bucket(lowest - 1)), lowest - 1);
+            assert_in_bucket(Histogram::highest_in(Histogram::bucket(lowest)), lowest);
+        }
+
+        // Quantiles never exceed the largest recorded value
+        let mut histogram = Histogram::new();
+        histogram.record(64);
+        assert_eq!(histogram.quantile(0.5), 64);
+        histogram.record(66);
+        assert_eq!(histogram.quantile(0.5), 65);
+        assert_eq!(histogram.quantile(1.0), 66);
+    }
+
+    #[test]
+    fn merge_matches_recording_everything() {
+        let mut all = Histogram::new();
+        let mut even = Histogram::new();
+        let mut odd = Histogram::new();
+        for value in (0..100_000).map(|i| i * 37) {
+            all.record(value);
+            if value % 2 == 0 {
+                even.record(value);
+            } else {
+                odd.record(value);
+            }
+        }
+        even.merge(&odd);
+        assert_eq!(even.buckets, all.buckets);
+        assert_eq!(even.count(), all.count());
+        assert_eq!(even.max(), all.max());
+        for quantile in [0.5, 0.99, 0.999] {
+            assert_eq!(even.quantile(quantile), all.quantile(quantile));
+        }
+    }
+}
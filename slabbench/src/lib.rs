@@ -1,3 +1,24 @@
+pub mod alloc;
+pub mod audit;
+pub mod backend;
+pub mod cli;
+pub mod compare;
+pub mod environment;
+pub mod generator;
+pub mod history;
+pub mod isolation;
+pub mod latency;
+pub mod ops;
+pub mod payload;
+pub mod phase;
+pub mod recording;
+pub mod results;
+pub mod revisions;
+pub mod run;
+pub mod scenario;
+pub mod trace;
+pub mod workload;
+
 use slab::Slab;
 use stable_vec::StableVec;
 
@@ -11,9 +32,6 @@ pub fn create_slab_with_elements(count: usize) -> (Slab<usize>, Vec<usize>) {
 /// Helper function to create a pre-populated stable_vec
 pub fn create_stable_vec_with_elements(count: usize) -> (StableVec<usize>, Vec<usize>) {
     let mut stable_vec = StableVec::new();
-    let keys: Vec<_> = (0..count).map(|i| {
-        let key = stable_vec.push(i);
-        key
-    }).collect();
+    let keys: Vec<_> = (0..count).map(|i| stable_vec.push(i)).collect();
     (stable_vec, keys)
 }
\ No newline at end of file
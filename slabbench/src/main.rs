@@ -0,0 +1,59 @@
+//! Command-line entry point for running workloads without the Criterion
+//! harness, e.g. on build servers or under `perf` and `heaptrack`.
+
+use std::ffi::OsString;
+use std::process::ExitCode;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use slabbench::backend::Builtin;
+use slabbench::cli;
+use slabbench::revisions::{self, Submodule};
+
+fn revisions_command() -> Command {
+    Command::new("revisions")
+        .about("Run a command against other local revisions of the submodules as extra backends")
+        .arg(
+            Arg::new("slab")
+                .long("slab")
+                .value_name("REV")
+                .help("Revision of slab to add as backend slab@<commit>; may be repeated")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("stable-vec")
+                .long("stable-vec")
+                .value_name("REV")
+                .help("Revision of stable-vec to add as backend stable_vec@<commit>; may be repeated")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            Arg::new("args")
+                .help("The command to run, e.g. run --scenario high_churn_workload")
+                .required(true)
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(OsString)),
+        )
+}
+
+/// Builds and runs the harness for the requested revisions.
+fn run_revisions(matches: &ArgMatches) -> std::io::Result<bool> {
+    let mut revs = Vec::new();
+    for (id, submodule) in [("slab", Submodule::Slab), ("stable-vec", Submodule::StableVec)] {
+        for rev in matches.get_many::<String>(id).into_iter().flatten() {
+            revs.push((submodule, rev.clone()));
+        }
+    }
+    let args: Vec<OsString> = matches.get_many::<OsString>("args").unwrap().cloned().collect();
+    revisions::run(&revs, &args)
+}
+
+fn main() -> ExitCode {
+    let matches = cli::command(&Builtin).subcommand(revisions_command()).get_matches();
+    let result = match matches.subcommand() {
+        Some(("revisions", matches)) => run_revisions(matches),
+        _ => cli::dispatch(&Builtin, &matches),
+    };
+    cli::exit_code(result)
+}
@@ -0,0 +1,195 @@
+//! Counting the operations a workload performs.
+//!
+//! Throughput is only comparable across workloads if it counts what each run
+//! actually does: a high churn run performs many times `size` operations, and
+//! how many depends on the pattern and even on the backend (an `insert_at`
+//! is skipped when the slot is occupied, say). Wrapping a backend in
+//! [`Counted`] counts every operation on this thread; call [`reset`] after
+//! setup and [`take`] once the measured part is done, as with
+//! [`crate::latency`].
+
+use std::cell::RefCell;
+
+use serde::Serialize;
+
+use crate::backend::Slabbable;
+
+/// The number of operations of each kind performed by a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OpCounts {
+    pub insert: u64,
+    pub insert_at: u64,
+    pub remove: u64,
+    /// Calls to `get`, `get_mut` and `contains`.
+    pub get: u64,
+    /// Elements yielded by `iter`, `iter_mut`, `keys` and `values`, so that
+    /// iterating counts like one lookup per live element.
+    pub iterated: u64,
+    pub shrink: u64,
+    pub clear: u64,
+}
+
+impl OpCounts {
+    /// The total number of operations.
+    pub fn total(&self) -> u64 {
+        self.insert + self.insert_at + self.remove + self.get + self.iterated + self.shrink + self.clear
+    }
+}
+
+thread_local! {
+    static COUNTS: RefCell<OpCounts> = RefCell::new(OpCounts::default());
+}
+
+/// Discards everything counted on this thread so far.
+pub fn reset() {
+    COUNTS.set(OpCounts::default());
+}
+
+/// Returns everything counted on this thread since the last [`reset`] or
+/// `take`, and starts over.
+pub fn take() -> OpCounts {
+    COUNTS.take()
+}
+
+#[inline(always)]
+fn count(op: impl FnOnce(&mut OpCounts)) {
+    COUNTS.with_borrow_mut(op);
+}
+
+/// An iterator that adds the number of elements it yielded to the counts
+/// when it is dropped.
+struct CountedIter<I> {
+    inner: I,
+    yielded: u64,
+}
+
+impl<I> CountedIter<I> {
+    #[inline(always)]
+    fn new(inner: I) -> Self {
+        Self { inner, yielded: 0 }
+    }
+}
+
+impl<I: Iterator> Iterator for CountedIter<I> {
+    type Item = I::Item;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.inner.next()?;
+        self.yielded += 1;
+        Some(item)
+    }
+}
+
+impl<I> Drop for CountedIter<I> {
+    fn drop(&mut self) {
+        let yielded = self.yielded;
+        count(|counts| counts.iterated += yielded);
+    }
+}
+
+/// A [`Slabbable`] wrapper that counts every operation on this thread.
+/// `vacant_key`, `len`, `capacity` and construction are not counted.
+#[derive(Debug, Default)]
+pub struct Counted<S>(pub S);
+
+impl<T: Default, S: Slabbable<T>> Slabbable<T> for Counted<S> {
+    fn new_with_capacity(capacity: usize) -> Self {
+        Counted(S::new_with_capacity(capacity))
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, value: T) -> usize {
+        count(|counts| counts.insert += 1);
+        self.0.insert(value)
+    }
+
+    #[inline(always)]
+    fn vacant_key(&self) -> usize {
+        self.0.vacant_key()
+    }
+
+    #[inline(always)]
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        count(|counts| counts.insert_at += 1);
+        self.0.insert_at(key, value)
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, key: usize) -> Option<T> {
+        count(|counts| counts.remove += 1);
+        self.0.remove(key)
+    }
+
+    #[inline(always)]
+    fn get(&self, key: usize) -> Option<&T> {
+        count(|counts| counts.get += 1);
+        self.0.get(key)
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        count(|counts| counts.get += 1);
+        self.0.get_mut(key)
+    }
+
+    #[inline(always)]
+    fn contains(&self, key: usize) -> bool {
+        count(|counts| counts.get += 1);
+        self.0.contains(key)
+    }
+
+    #[inline(always)]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline(always)]
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    #[inline(always)]
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        CountedIter::new(self.0.iter())
+    }
+
+    #[inline(always)]
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        CountedIter::new(self.0.iter_mut())
+    }
+
+    #[inline(always)]
+    fn keys<'a>(&'a self) -> impl Iterator<Item = usize>
+    where
+        T: 'a,
+    {
+        CountedIter::new(self.0.keys())
+    }
+
+    #[inline(always)]
+    fn values<'a>(&'a self) -> impl Iterator<Item = &'a T>
+    where
+        T: 'a,
+    {
+        CountedIter::new(self.0.values())
+    }
+
+    #[inline(always)]
+    fn clear(&mut self) {
+        count(|counts| counts.clear += 1);
+        self.0.clear()
+    }
+
+    #[inline(always)]
+    fn shrink(&mut self) {
+        count(|counts| counts.shrink += 1);
+        self.0.shrink()
+    }
+}
@@ -0,0 +1,124 @@
+//! Element types stored by the workloads.
+//!
+//! Every workload is generic over a [`Payload`], so the same operations can be
+//! measured with elements of different sizes and drop costs. This matters
+//! because the backends lay out vacant slots differently: `slab::Entry<T>` is
+//! an enum the size of `T` plus a tag, while `StableVec` keeps `T` in
+//! uninitialized storage next to a separate bitvector.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A value that a workload can store.
+pub trait Payload: Default + 'static {
+    /// Builds the value stored by the `index`-th insert.
+    fn from_index(index: usize) -> Self;
+
+    /// A cheap summary of the value, folded into the sums that keep lookups
+    /// from being optimized away. For `usize` this is the value itself.
+    fn checksum(&self) -> usize;
+}
+
+impl Payload for usize {
+    fn from_index(index: usize) -> Self {
+        index
+    }
+
+    fn checksum(&self) -> usize {
+        *self
+    }
+}
+
+/// Plain old data of `WORDS` machine words, e.g. 64 bytes for `Pod<8>` on
+/// 64-bit targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pod<const WORDS: usize>(pub [usize; WORDS]);
+
+impl<const WORDS: usize> Default for Pod<WORDS> {
+    fn default() -> Self {
+        Pod([0; WORDS])
+    }
+}
+
+impl<const WORDS: usize> Payload for Pod<WORDS> {
+    fn from_index(index: usize) -> Self {
+        Pod([index; WORDS])
+    }
+
+    fn checksum(&self) -> usize {
+        self.0[0]
+    }
+}
+
+/// A 64-byte [`Pod`] on 64-bit targets.
+pub type Pod64 = Pod<8>;
+
+/// A 256-byte [`Pod`] on 64-bit targets.
+pub type Pod256 = Pod<32>;
+
+/// Heap-allocated strings: every insert allocates and every removal frees.
+impl Payload for String {
+    fn from_index(index: usize) -> Self {
+        index.to_string()
+    }
+
+    fn checksum(&self) -> usize {
+        // Read through the pointer so lookups touch the heap allocation
+        self.len() + self.as_bytes().first().map_or(0, |&b| b as usize)
+    }
+}
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// A word-sized value whose `Drop` does observable work, so backends cannot
+/// skip drop glue when removing, clearing or dropping elements.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DropCounter(pub usize);
+
+impl DropCounter {
+    /// Number of `DropCounter`s dropped so far by this process.
+    pub fn drops() -> usize {
+        DROPS.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Payload for DropCounter {
+    fn from_index(index: usize) -> Self {
+        DropCounter(index)
+    }
+
+    fn checksum(&self) -> usize {
+        self.0
+    }
+}
+
+/// Names of every payload accepted by [`visit_payload`]. `usize` is the
+/// 8-byte plain-old-data payload and the default.
+pub const PAYLOADS: [&str; 5] = ["usize", "pod64", "pod256", "string", "drop_counter"];
+
+/// Code that is generic over the payload type, for running it with a payload
+/// chosen by name at runtime with [`visit_payload`].
+pub trait PayloadVisitor {
+    type Output;
+
+    fn visit<T: Payload>(self, name: &'static str) -> Self::Output;
+}
+
+/// Calls `visitor` with the payload called `name`, or returns `None` if there
+/// is no such payload.
+pub fn visit_payload<V: PayloadVisitor>(name: &str, visitor: V) -> Option<V::Output> {
+    let output = match name {
+        "usize" => visitor.visit::<usize>("usize"),
+        "pod64" => visitor.visit::<Pod64>("pod64"),
+        "pod256" => visitor.visit::<Pod256>("pod256"),
+        "string" => visitor.visit::<String>("string"),
+        "drop_counter" => visitor.visit::<DropCounter>("drop_counter"),
+        _ => return None,
+    };
+    Some(output)
+}
@@ -0,0 +1,114 @@
+//! Per-phase timing inside a workload run.
+//!
+//! The mixed workloads interleave inserts, lookups, removals and iteration,
+//! so a single time per run cannot say which kind of work one backend is
+//! faster at. Each workload marks the end of every phase with
+//! [`Phases::lap`], which attributes the time since the previous lap to the
+//! named phase. Phases that repeat, like the removals of every high churn
+//! cycle, accumulate under one name.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Time spent in each named phase, over one or more runs.
+#[derive(Debug, Clone)]
+pub struct Phases {
+    times: Vec<(&'static str, Duration)>,
+    runs: u64,
+    /// When the previous lap ended, or `None` if timing is disabled.
+    last: Option<Instant>,
+}
+
+impl Default for Phases {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Phases {
+    /// Starts timing one run: the first phase begins now.
+    pub fn new() -> Self {
+        Self {
+            times: Vec::new(),
+            runs: 1,
+            last: Some(Instant::now()),
+        }
+    }
+
+    /// An empty tracker that ignores laps, for runs that are only timed as a
+    /// whole or for accumulating other runs with [`merge`](Self::merge).
+    pub fn disabled() -> Self {
+        Self {
+            times: Vec::new(),
+            runs: 0,
+            last: None,
+        }
+    }
+
+    /// Ends the current phase, attributing the time since the previous lap
+    /// (or since [`new`](Self::new)) to `phase`.
+    #[inline]
+    pub fn lap(&mut self, phase: &'static str) {
+        let Some(last) = self.last else {
+            return;
+        };
+        let now = Instant::now();
+        self.add(phase, now - last);
+        self.last = Some(now);
+    }
+
+    fn add(&mut self, phase: &'static str, time: Duration) {
+        // Workloads have a handful of phases, so a linear scan is cheapest
+        match self.times.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += time,
+            None => self.times.push((phase, time)),
+        }
+    }
+
+    /// Adds the phase times and runs of `other`, keeping phases in the order
+    /// they were first seen.
+    pub fn merge(&mut self, other: &Phases) {
+        for &(phase, time) in &other.times {
+            self.add(phase, time);
+        }
+        self.runs += other.runs;
+    }
+
+    /// Number of runs whose phases were recorded or merged.
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// Total time of every phase, in the order the phases first ran.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Duration)> + '_ {
+        self.times.iter().copied()
+    }
+
+    /// Total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.times.iter().map(|&(_, time)| time).sum()
+    }
+
+    /// The mean time per run of every phase and its share of the total.
+    pub fn summaries(&self) -> Vec<PhaseTime> {
+        let total = self.total().as_nanos().max(1) as f64;
+        let runs = self.runs.max(1) as u128;
+        self.iter()
+            .map(|(phase, time)| PhaseTime {
+                phase,
+                mean_ns: (time.as_nanos() / runs) as u64,
+                share: time.as_nanos() as f64 / total,
+            })
+            .collect()
+    }
+}
+
+/// How long one phase took per run on average.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTime {
+    pub phase: &'static str,
+    pub mean_ns: u64,
+    /// Fraction of the whole run spent in this phase, in `[0, 1]`.
+    pub share: f64,
+}
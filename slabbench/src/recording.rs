@@ -0,0 +1,274 @@
+//! A drop-in replacement for `slab::Slab` that logs every call.
+//!
+//! [`Recording`] exposes the same methods as `slab::Slab` and streams each
+//! call to a writer in the text trace format from [`crate::trace`], so a trace
+//! can be captured from a running application and then replayed by the
+//! benchmark harness. A typical setup swaps it in behind a cargo feature:
+//!
+//! ```ignore
+//! #[cfg(feature = "record-slab")]
+//! type Connections = slabbench::recording::Recording<Connection>;
+//! #[cfg(not(feature = "record-slab"))]
+//! type Connections = slab::Slab<Connection>;
+//! ```
+//!
+//! Keys are logged as returned by the wrapped backend, which is what
+//! [`Trace::replay`](crate::trace::Trace::replay) expects.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::path::Path;
+
+use crate::backend::{SlabWrapper, Slabbable};
+use crate::trace::{Op, TEXT_HEADER};
+
+/// A `Slab`-like collection that logs every call to a writer.
+///
+/// Write errors cannot be reported through the `Slab` API, so the first one is
+/// kept, logging stops, and it is returned by [`Recording::finish`]. Values
+/// must implement `Default`, as for every [`Slabbable`] backend.
+pub struct Recording<T, S = SlabWrapper<T>, W: Write = BufWriter<File>> {
+    inner: S,
+    log: RefCell<Log<W>>,
+    _marker: PhantomData<T>,
+}
+
+struct Log<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Log<W> {
+    fn write(&mut self, line: &dyn std::fmt::Display) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.writer, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+}
+
+impl<T: Default> Recording<T> {
+    /// Creates an empty recording collection that logs to the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<T: Default, S: Slabbable<T>, W: Write> Recording<T, S, W> {
+    /// Creates an empty recording collection that logs to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self::with_capacity(0, writer)
+    }
+
+    /// Creates an empty recording collection with room for `capacity` values.
+    pub fn with_capacity(capacity: usize, writer: W) -> Self {
+        let mut log = Log { writer, error: None };
+        log.write(&TEXT_HEADER);
+        Self {
+            inner: S::new_with_capacity(capacity),
+            log: RefCell::new(log),
+            _marker: PhantomData,
+        }
+    }
+
+    fn record(&self, op: Op) {
+        self.log.borrow_mut().write(&op);
+    }
+
+    /// Inserts `value` and returns its key.
+    pub fn insert(&mut self, value: T) -> usize {
+        let key = self.inner.insert(value);
+        self.record(Op::Insert { key });
+        key
+    }
+
+    /// Returns a handle to the slot that the next insert will use.
+    pub fn vacant_entry(&mut self) -> VacantEntry<'_, T, S, W> {
+        VacantEntry {
+            key: self.inner.vacant_key(),
+            recording: self,
+        }
+    }
+
+    /// Returns the key that the next insert will use.
+    pub fn vacant_key(&self) -> usize {
+        self.inner.vacant_key()
+    }
+
+    /// Removes and returns the value at `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is vacant, like `Slab::remove`.
+    pub fn remove(&mut self, key: usize) -> T {
+        // Check before logging, so a panicking call leaves no op in the trace
+        assert!(self.inner.contains(key), "invalid key");
+        self.try_remove(key).expect("invalid key")
+    }
+
+    /// Removes and returns the value at `key`, if any.
+    pub fn try_remove(&mut self, key: usize) -> Option<T> {
+        self.record(Op::Remove { key });
+        self.inner.remove(key)
+    }
+
+    /// Returns a reference to the value at `key`, if any.
+    pub fn get(&self, key: usize) -> Option<&T> {
+        self.record(Op::Get { key });
+        self.inner.get(key)
+    }
+
+    /// Returns a mutable reference to the value at `key`, if any.
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.record(Op::Get { key });
+        self.inner.get_mut(key)
+    }
+
+    /// Returns `true` if a value is stored at `key`.
+    pub fn contains(&self, key: usize) -> bool {
+        self.record(Op::Get { key });
+        self.inner.contains(key)
+    }
+
+    /// Iterates over all stored `(key, value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.record(Op::Iterate);
+        self.inner.iter()
+    }
+
+    /// Iterates over all stored `(key, value)` pairs with mutable access to
+    /// the values.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.record(Op::Iterate);
+        self.inner.iter_mut()
+    }
+
+    /// Returns the number of stored values.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the number of slots available without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Removes all values.
+    pub fn clear(&mut self) {
+        self.record(Op::Clear);
+        self.inner.clear()
+    }
+
+    /// Releases unused memory without changing any key.
+    pub fn shrink_to_fit(&mut self) {
+        self.record(Op::Shrink);
+        self.inner.shrink()
+    }
+
+    /// Flushes the log and returns the wrapped collection and writer, or the
+    /// first write error encountered while logging.
+    pub fn finish(self) -> io::Result<(S, W)> {
+        let Log { mut writer, error } = self.log.into_inner();
+        if let Some(e) = error {
+            return Err(e);
+        }
+        writer.flush()?;
+        Ok((self.inner, writer))
+    }
+}
+
+impl<T: Default, S: Slabbable<T>, W: Write> Index<usize> for Recording<T, S, W> {
+    type Output = T;
+
+    /// Returns a reference to the value at `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is vacant, like indexing a `Slab`.
+    fn index(&self, key: usize) -> &T {
+        self.get(key).expect("invalid key")
+    }
+}
+
+impl<T: Default, S: Slabbable<T>, W: Write> IndexMut<usize> for Recording<T, S, W> {
+    /// Returns a mutable reference to the value at `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is vacant, like indexing a `Slab`.
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        self.get_mut(key).expect("invalid key")
+    }
+}
+
+/// A handle to the slot that the next insert into a [`Recording`] will use.
+pub struct VacantEntry<'a, T, S, W: Write> {
+    recording: &'a mut Recording<T, S, W>,
+    key: usize,
+}
+
+impl<'a, T: Default, S: Slabbable<T>, W: Write> VacantEntry<'a, T, S, W> {
+    /// Returns the key this entry will be inserted at.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Inserts `value` at this entry's key and returns a reference to it.
+    pub fn insert(self, value: T) -> &'a mut T {
+        let recording = self.recording;
+        let key = recording.insert(value);
+        debug_assert_eq!(key, self.key);
+        recording.inner.get_mut(key).expect("value was just inserted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::*;
+    use crate::trace::Trace;
+
+    #[test]
+    fn replay_reproduces_recorded_state() {
+        let mut recording: Recording<usize, SlabWrapper<usize>, Vec<u8>> = Recording::new(Vec::new());
+        let a = recording.insert(0);
+        let b = recording.insert(1);
+        let c = recording.vacant_entry().key();
+        recording.vacant_entry().insert(2);
+        recording[b] = 1;
+        assert_eq!(recording[c], 2);
+        recording.remove(a);
+        recording.insert(6);
+        recording.shrink_to_fit();
+        let (recorded, log) = recording.finish().unwrap();
+
+        let trace = Trace::read_text(&log[..]).unwrap();
+        let mut replayed = SlabWrapper::<usize>::new_with_capacity(0);
+        trace.replay(&mut replayed);
+        // Replayed values are the index of the inserting operation, which the
+        // recorded values above were chosen to match
+        let state = |backend: &SlabWrapper<usize>| backend.iter().map(|(key, &val)| (key, val)).collect::<Vec<_>>();
+        assert_eq!(state(&replayed), state(&recorded));
+    }
+
+    #[test]
+    fn panicking_remove_is_not_recorded() {
+        let mut recording: Recording<usize, SlabWrapper<usize>, Vec<u8>> = Recording::new(Vec::new());
+        recording.insert(0);
+        let removed = panic::catch_unwind(AssertUnwindSafe(|| recording.remove(1)));
+        assert!(removed.is_err());
+        let (_, log) = recording.finish().unwrap();
+        assert_eq!(Trace::read_text(&log[..]).unwrap().ops, [Op::Insert { key: 0 }]);
+    }
+}
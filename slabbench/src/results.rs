@@ -0,0 +1,409 @@
+//! Reading Criterion's saved results and rendering them as README tables.
+//!
+//! Criterion keeps the estimates from its latest run of every benchmark in
+//! `target/criterion/<group>/<function>/<parameter>/new/estimates.json`, next
+//! to a `benchmark.json` that names the benchmark. [`Results::load`] reads
+//! all of them, and [`rewrite_tables`] regenerates every table in a Markdown
+//! document that sits between markers like these:
+//!
+//! ```text
+//! <!-- slabbench-table group=high_churn_workload case=uniform -->
+//! <!-- /slabbench-table -->
+//! ```
+//!
+//! A table compares a candidate backend against a baseline backend for one
+//! case of a group, with one row per parameter (usually the size). Each cell
+//! is Criterion's estimate of the mean with the half-width of its 95%
+//! confidence interval, and the last column is the relative difference of
+//! the means with the bounds implied by both intervals. The marker may set
+//! `baseline=` and `candidate=` to compare other backends than `slab` and
+//! `stable_vec`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::run::{format_ns, write_markdown};
+
+/// Where the README lives.
+pub const README: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../README.md");
+
+const OPEN_MARKER: &str = "<!-- slabbench-table";
+const CLOSE_MARKER: &str = "<!-- /slabbench-table -->";
+
+/// Cargo's target directory: `CARGO_TARGET_DIR` if that is set, or else the
+/// workspace's `target` directory.
+pub fn target_dir() -> PathBuf {
+    std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../target"))
+}
+
+/// Criterion's output directory, `criterion` in the [`target_dir`].
+pub fn criterion_dir() -> PathBuf {
+    target_dir().join("criterion")
+}
+
+/// Finds every benchmark directory below `dir` with results saved under
+/// `name`, i.e. every directory with a `<name>/benchmark.json`, sorted by
+/// path. Criterion saves its latest results as `new`, the previous ones as
+/// `base`, and named baselines (`--save-baseline`) under their name.
+pub fn benchmark_dirs(dir: impl AsRef<Path>, name: &str) -> io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, name: &str, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        if dir.join(name).join("benchmark.json").is_file() {
+            found.push(dir.to_path_buf());
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            // Criterion's HTML reports never contain benchmarks
+            if entry.file_type()?.is_dir() && entry.file_name() != "report" {
+                walk(&entry.path(), name, found)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    walk(dir.as_ref(), name, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+/// Reads and parses a JSON file written by Criterion.
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Identifies one benchmark, as in Criterion's `group/function/parameter`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub struct BenchmarkId {
+    #[serde(rename = "group_id")]
+    pub group: String,
+    #[serde(rename = "function_id", default)]
+    pub function: Option<String>,
+    #[serde(rename = "value_str", default)]
+    pub parameter: Option<String>,
+}
+
+impl BenchmarkId {
+    /// Reads the ID of the benchmark in `dir` from its results saved under
+    /// `name`.
+    pub fn load(dir: &Path, name: &str) -> io::Result<Self> {
+        read_json(&dir.join(name).join("benchmark.json"))
+    }
+}
+
+impl fmt::Display for BenchmarkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.group)?;
+        for part in [&self.function, &self.parameter].into_iter().flatten() {
+            write!(f, "/{}", part)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Statistic,
+}
+
+#[derive(Deserialize)]
+struct Statistic {
+    point_estimate: f64,
+    confidence_interval: ConfidenceInterval,
+}
+
+#[derive(Deserialize)]
+struct ConfidenceInterval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+/// Criterion's estimate of a benchmark's mean time per iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Estimate {
+    pub mean_ns: f64,
+    /// Bounds of the 95% confidence interval.
+    pub lower_ns: f64,
+    pub upper_ns: f64,
+}
+
+impl Estimate {
+    /// Reads the estimate of the benchmark in `dir` from its results saved
+    /// under `name`.
+    pub fn load(dir: &Path, name: &str) -> io::Result<Self> {
+        let Estimates { mean } = read_json(&dir.join(name).join("estimates.json"))?;
+        Ok(Self {
+            mean_ns: mean.point_estimate,
+            lower_ns: mean.confidence_interval.lower_bound,
+            upper_ns: mean.confidence_interval.upper_bound,
+        })
+    }
+}
+
+/// The estimates of every benchmark in a Criterion output directory.
+#[derive(Debug, Clone, Default)]
+pub struct Results {
+    estimates: BTreeMap<BenchmarkId, Estimate>,
+}
+
+impl Results {
+    /// Loads the latest estimates of every benchmark below `dir`.
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut estimates = BTreeMap::new();
+        for dir in benchmark_dirs(dir, "new")? {
+            estimates.insert(BenchmarkId::load(&dir, "new")?, Estimate::load(&dir, "new")?);
+        }
+        Ok(Self { estimates })
+    }
+
+    /// The estimate for `function` in `group` at `parameter`, if there is one.
+    pub fn get(&self, group: &str, function: &str, parameter: &str) -> Option<Estimate> {
+        let id = BenchmarkId {
+            group: group.to_string(),
+            function: Some(function.to_string()),
+            parameter: Some(parameter.to_string()),
+        };
+        self.estimates.get(&id).copied()
+    }
+
+    /// The parameters `function` in `group` was run with, sorted numerically
+    /// where they are numbers.
+    pub fn parameters(&self, group: &str, function: &str) -> Vec<&str> {
+        let mut parameters: Vec<&str> = self
+            .estimates
+            .keys()
+            .filter(|id| id.group == group && id.function.as_deref() == Some(function))
+            .filter_map(|id| id.parameter.as_deref())
+            .collect();
+        parameters.sort_by_key(|parameter| (parameter.parse::<u64>().ok(), parameter.to_string()));
+        parameters
+    }
+}
+
+/// A table requested by a marker in the README.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSpec {
+    pub group: String,
+    /// The case label, e.g. `uniform`, or empty for a group's only case.
+    pub case: String,
+    pub baseline: String,
+    pub candidate: String,
+}
+
+impl TableSpec {
+    /// Parses the `key=value` pairs of a marker.
+    pub fn parse(marker: &str) -> Result<Self, String> {
+        let mut spec = TableSpec {
+            group: String::new(),
+            case: String::new(),
+            baseline: "slab".to_string(),
+            candidate: "stable_vec".to_string(),
+        };
+        for pair in marker.split_whitespace() {
+            let Some((key, value)) = pair.split_once('=') else {
+                return Err(format!("expected key=value, found '{}'", pair));
+            };
+            match key {
+                "group" => spec.group = value.to_string(),
+                "case" => spec.case = value.to_string(),
+                "baseline" => spec.baseline = value.to_string(),
+                "candidate" => spec.candidate = value.to_string(),
+                _ => return Err(format!("unknown table option '{}'", key)),
+            }
+        }
+        if spec.group.is_empty() {
+            return Err("a table needs a group".to_string());
+        }
+        Ok(spec)
+    }
+
+    /// The Criterion function ID of this table's case on `backend`.
+    fn function(&self, backend: &str) -> String {
+        if self.case.is_empty() {
+            backend.to_string()
+        } else {
+            format!("{}_{}", backend, self.case)
+        }
+    }
+}
+
+/// The name of a backend's collection type, for table headers.
+fn display_name(backend: &str) -> &str {
+    match backend {
+        "slab" => "Slab",
+        "stable_vec" => "StableVec",
+        "stable_vec_reuse" => "StableVec (reusing)",
+        "inline_stable_vec" => "InlineStableVec",
+        "vec_option" => "Vec<Option<T>>",
+        "hash_map" => "HashMap",
+        "btree_map" => "BTreeMap",
+        _ => backend,
+    }
+}
+
+/// Formats an integer with thousands separators, e.g. `10,000`, or returns
+/// anything else unchanged.
+fn with_separators(parameter: &str) -> String {
+    if parameter.is_empty() || !parameter.bytes().all(|b| b.is_ascii_digit()) {
+        return parameter.to_string();
+    }
+    let mut formatted = String::new();
+    for (i, digit) in parameter.chars().enumerate() {
+        if i > 0 && (parameter.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+fn format_estimate(estimate: Estimate) -> String {
+    let half_width = (estimate.upper_ns - estimate.lower_ns) / 2.0;
+    format!("{} ± {:.1}%", format_ns(estimate.mean_ns), half_width / estimate.mean_ns * 100.0)
+}
+
+/// Describes how `candidate` compares to `baseline`, e.g.
+/// `20.4% faster (-20.9% to -19.9%)`. The bounds are the most extreme ratios
+/// of the two confidence intervals.
+fn format_change(baseline: Estimate, candidate: Estimate) -> String {
+    let change = (candidate.mean_ns / baseline.mean_ns - 1.0) * 100.0;
+    let lower = (candidate.lower_ns / baseline.upper_ns - 1.0) * 100.0;
+    let upper = (candidate.upper_ns / baseline.lower_ns - 1.0) * 100.0;
+    let direction = if change <= 0.0 { "faster" } else { "slower" };
+    format!("{:.1}% {} ({:+.1}% to {:+.1}%)", change.abs(), direction, lower, upper)
+}
+
+/// Renders the table described by `spec` as Markdown, with a row for every
+/// parameter that both backends were run with.
+pub fn render_table(results: &Results, spec: &TableSpec) -> Result<String, String> {
+    let (baseline_function, candidate_function) = (spec.function(&spec.baseline), spec.function(&spec.candidate));
+    let (baseline_name, candidate_name) = (display_name(&spec.baseline), display_name(&spec.candidate));
+
+    let mut rows: Vec<[String; 4]> = Vec::new();
+    for parameter in results.parameters(&spec.group, &baseline_function) {
+        let baseline = results.get(&spec.group, &baseline_function, parameter);
+        let candidate = results.get(&spec.group, &candidate_function, parameter);
+        if let (Some(baseline), Some(candidate)) = (baseline, candidate) {
+            rows.push([
+                with_separators(parameter),
+                format_estimate(baseline),
+                format_estimate(candidate),
+                format_change(baseline, candidate),
+            ]);
+        }
+    }
+    if rows.is_empty() {
+        return Err(format!(
+            "no results for both {0}/{1} and {0}/{2}",
+            spec.group, baseline_function, candidate_function
+        ));
+    }
+
+    let header = [
+        "Collection Size".to_string(),
+        baseline_name.to_string(),
+        candidate_name.to_string(),
+        format!("{} vs {}", candidate_name, baseline_name),
+    ];
+    let mut table = Vec::new();
+    let header = header.each_ref().map(String::as_str);
+    write_markdown(&mut table, &header, &rows).expect("writing to a Vec cannot fail");
+    Ok(String::from_utf8(table).expect("the table is built from strings"))
+}
+
+/// The position of the first opening marker in `text` that starts a line,
+/// where `text` itself starts a line if `at_line_start` is set.
+fn find_open_marker(text: &str, at_line_start: bool) -> Option<usize> {
+    text.match_indices(OPEN_MARKER)
+        .map(|(i, _)| i)
+        .find(|&i| if i == 0 { at_line_start } else { text[..i].ends_with('\n') })
+}
+
+/// Regenerates every marked table in `document` from `results`, leaving
+/// everything outside the markers untouched. Markers only count at the start
+/// of a line, so one quoted in the middle of a sentence is left alone.
+pub fn rewrite_tables(document: &str, results: &Results) -> Result<String, String> {
+    let mut rewritten = String::with_capacity(document.len());
+    let mut rest = document;
+    let mut at_line_start = true;
+    while let Some(start) = find_open_marker(rest, at_line_start) {
+        let after_open = &rest[start + OPEN_MARKER.len()..];
+        let Some(open_end) = after_open.find("-->") else {
+            return Err("unterminated table marker".to_string());
+        };
+        let spec = TableSpec::parse(&after_open[..open_end])?;
+        let after_marker = &after_open[open_end + "-->".len()..];
+        let Some(close) = after_marker.find(CLOSE_MARKER) else {
+            return Err(format!("table for {} is missing its closing marker", spec.group));
+        };
+
+        rewritten.push_str(&rest[..start + OPEN_MARKER.len() + open_end + "-->".len()]);
+        rewritten.push('\n');
+        rewritten.push_str(&render_table(results, &spec)?);
+        rewritten.push_str(CLOSE_MARKER);
+        rest = &after_marker[close + CLOSE_MARKER.len()..];
+        at_line_start = false;
+    }
+    rewritten.push_str(rest);
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Results {
+        let mut estimates = BTreeMap::new();
+        for (function, parameter, mean_ns) in
+            [("slab", "100", 10.0), ("stable_vec", "100", 12.0), ("slab", "10000", 20.0), ("stable_vec", "10000", 18.0)]
+        {
+            let id = BenchmarkId {
+                group: "insert".to_string(),
+                function: Some(function.to_string()),
+                parameter: Some(parameter.to_string()),
+            };
+            let estimate = Estimate {
+                mean_ns,
+                lower_ns: mean_ns * 0.99,
+                upper_ns: mean_ns * 1.01,
+            };
+            estimates.insert(id, estimate);
+        }
+        Results { estimates }
+    }
+
+    #[test]
+    fn missing_close_marker_is_an_error() {
+        let document = "# Results\n\n<!-- slabbench-table group=insert -->\n| stale |\n\nMore text.\n";
+        assert!(rewrite_tables(document, &results()).is_err());
+
+        let unterminated = "# Results\n\n<!-- slabbench-table group=insert\n";
+        assert!(rewrite_tables(unterminated, &results()).is_err());
+    }
+
+    #[test]
+    fn quoted_markers_are_ignored() {
+        let document = "Tables sit between `<!-- slabbench-table ... -->` markers.\n";
+        assert_eq!(rewrite_tables(document, &results()).unwrap(), document);
+    }
+
+    #[test]
+    fn rewriting_is_idempotent() {
+        let document = "# Results\n\n<!-- slabbench-table group=insert -->\n| stale |\n<!-- /slabbench-table -->\n\nMore text.\n";
+        let rewritten = rewrite_tables(document, &results()).unwrap();
+        assert!(rewritten.starts_with("# Results\n\n<!-- slabbench-table group=insert -->\n| Collection Size "));
+        assert!(rewritten.contains("| 10,000 "));
+        assert!(rewritten.ends_with("<!-- /slabbench-table -->\n\nMore text.\n"));
+        assert!(!rewritten.contains("stale"));
+        assert_eq!(rewrite_tables(&rewritten, &results()).unwrap(), rewritten);
+    }
+}
@@ -0,0 +1,253 @@
+//! Benchmarking other revisions of the `slab` and `stable-vec` submodules.
+//!
+//! To evaluate an upstream change, say to `Slab`'s free list, the workloads
+//! have to run against both the old and the new code under identical
+//! conditions. Each requested [`Revision`] is checked out into a git worktree
+//! of its submodule below `target/revisions`, and [`generate`] writes a small
+//! harness crate next to them that depends on `slabbench` and on every
+//! worktree under a crate alias such as `slab_1a2b3c4d5e`. Its binary is the
+//! usual command line from [`crate::cli`] with one more backend per revision,
+//! named like `slab@1a2b3c4d5e`, so all revisions run the same workloads in
+//! one process and show up in a single report.
+//!
+//! Nothing is fetched: revisions must already exist in the local clones of
+//! the submodules.
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::results;
+
+/// The workspace root, which contains the submodules.
+pub const WORKSPACE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+
+/// A submodule that can be benchmarked at other revisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Submodule {
+    Slab,
+    StableVec,
+}
+
+impl Submodule {
+    /// The directory of the submodule in the workspace, which is also the
+    /// name of its package.
+    pub fn dir(self) -> &'static str {
+        match self {
+            Submodule::Slab => "slab",
+            Submodule::StableVec => "stable-vec",
+        }
+    }
+
+    /// The name of the crate, which prefixes aliases and backend names.
+    pub fn crate_name(self) -> &'static str {
+        match self {
+            Submodule::Slab => "slab",
+            Submodule::StableVec => "stable_vec",
+        }
+    }
+
+    fn type_prefix(self) -> &'static str {
+        match self {
+            Submodule::Slab => "Slab",
+            Submodule::StableVec => "StableVec",
+        }
+    }
+
+    /// The macro in [`crate::backend`] that defines an adapter for the crate.
+    fn adapter_macro(self) -> &'static str {
+        match self {
+            Submodule::Slab => "slab_adapter",
+            Submodule::StableVec => "stable_vec_adapter",
+        }
+    }
+}
+
+/// A commit of a submodule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub submodule: Submodule,
+    /// The commit hash, abbreviated to ten digits.
+    pub commit: String,
+}
+
+impl Revision {
+    /// Resolves `rev`, a branch, tag or commit, in the local clone of
+    /// `submodule` below `workspace`.
+    pub fn resolve(workspace: &Path, submodule: Submodule, rev: &str) -> io::Result<Self> {
+        let dir = workspace.join(submodule.dir());
+        check_repository(&dir)?;
+        let commit = git(&dir, &["rev-parse", "--verify", "--short=10", &format!("{}^{{commit}}", rev)])
+        .map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("unknown {} revision '{}': {}", submodule.dir(), rev, e))
+        })?;
+        Ok(Self { submodule, commit })
+    }
+
+    /// The name the revision is a dependency under, e.g. `slab_1a2b3c4d5e`.
+    pub fn alias(&self) -> String {
+        format!("{}_{}", self.submodule.crate_name(), self.commit)
+    }
+
+    /// The name of the revision's backend, e.g. `slab@1a2b3c4d5e`.
+    pub fn backend(&self) -> String {
+        format!("{}@{}", self.submodule.crate_name(), self.commit)
+    }
+
+    fn type_name(&self) -> String {
+        format!("{}At{}", self.submodule.type_prefix(), self.commit)
+    }
+
+    /// The directory of the revision's worktree below `dir`.
+    pub fn worktree(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}-{}", self.submodule.dir(), self.commit))
+    }
+}
+
+/// Runs git in `dir` and returns its trimmed output.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Checks that `dir` is the root of a git repository of its own, and not,
+/// say, an uninitialized submodule, in which git would find the workspace's
+/// repository instead.
+pub(crate) fn check_repository(dir: &Path) -> io::Result<()> {
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+    if Path::new(&toplevel).canonicalize()? != dir.canonicalize()? {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not checked out; run `git submodule update --init`", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// The directory the worktrees and the harness are kept in,
+/// `target/revisions`.
+pub fn revisions_dir() -> PathBuf {
+    results::target_dir().join("revisions")
+}
+
+/// Checks `revision` out into a detached worktree below `dir`, unless it
+/// already is, and returns the worktree's path.
+pub fn checkout(workspace: &Path, dir: &Path, revision: &Revision) -> io::Result<PathBuf> {
+    let worktree = revision.worktree(dir);
+    if !worktree.join("Cargo.toml").is_file() {
+        fs::create_dir_all(dir)?;
+        let path = worktree.to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not valid UTF-8", worktree.display()))
+        })?;
+        let submodule = workspace.join(revision.submodule.dir());
+        git(&submodule, &["worktree", "add", "--force", "--detach", path, &revision.commit])?;
+    }
+    Ok(worktree)
+}
+
+/// Writes the harness crate for `revisions` to `dir/bench`, given the path
+/// of each revision's worktree, and returns the path of its manifest.
+pub fn generate(workspace: &Path, dir: &Path, revisions: &[(Revision, PathBuf)]) -> io::Result<PathBuf> {
+    let crate_dir = dir.join("bench");
+    fs::create_dir_all(crate_dir.join("src"))?;
+
+    let mut manifest = String::from("# Generated by `slabbench revisions`; do not edit.\n");
+    manifest.push_str("[package]\nname = \"slabbench-revisions\"\nversion = \"0.0.0\"\nedition = \"2024\"\n");
+    manifest.push_str("publish = false\n\n[workspace]\n\n[dependencies]\n");
+    writeln!(manifest, "slabbench = {{ path = {:?} }}", workspace.join("slabbench")).unwrap();
+    for (revision, worktree) in revisions {
+        writeln!(
+            manifest,
+            "{} = {{ package = {:?}, path = {:?} }}",
+            revision.alias(),
+            revision.submodule.dir(),
+            worktree
+        )
+        .unwrap();
+    }
+    fs::write(crate_dir.join("Cargo.toml"), manifest)?;
+    // Start from the workspace's lock file, so that the harness is built with
+    // the same versions of every other dependency
+    let lock = crate_dir.join("Cargo.lock");
+    if !lock.exists() {
+        fs::copy(workspace.join("Cargo.lock"), lock)?;
+    }
+
+    let mut main = String::from("//! Generated by `slabbench revisions`; do not edit.\n\n");
+    main.push_str("use slabbench::backend::{BackendSet, BackendVisitor, Builtin};\n\n");
+    for (revision, _) in revisions {
+        writeln!(
+            main,
+            "slabbench::{}! {{ struct {}({}); }}",
+            revision.submodule.adapter_macro(),
+            revision.type_name(),
+            revision.alias()
+        )
+        .unwrap();
+    }
+    main.push_str("\nstruct Revisions;\n\nimpl BackendSet for Revisions {\n");
+    main.push_str("    fn names(&self) -> Vec<&'static str> {\n        let mut names = Builtin.names();\n");
+    for (revision, _) in revisions {
+        writeln!(main, "        names.push({:?});", revision.backend()).unwrap();
+    }
+    main.push_str("        names\n    }\n\n");
+    main.push_str(
+        "    fn visit<T: Default, V: BackendVisitor<T>>(&self, name: &str, visitor: V) -> Option<V::Output> {\n",
+    );
+    main.push_str("        match name {\n");
+    for (revision, _) in revisions {
+        writeln!(
+            main,
+            "            {0:?} => Some(visitor.visit::<{1}<T>>({0:?})),",
+            revision.backend(),
+            revision.type_name()
+        )
+        .unwrap();
+    }
+    main.push_str("            _ => Builtin.visit(name, visitor),\n        }\n    }\n}\n\n");
+    main.push_str("fn main() -> std::process::ExitCode {\n    slabbench::cli::main(&Revisions)\n}\n");
+    fs::write(crate_dir.join("src/main.rs"), main)?;
+
+    Ok(crate_dir.join("Cargo.toml"))
+}
+
+/// Checks out `revs` of each submodule, generates the harness for them and
+/// runs it in release mode with `args`. Returns whether the harness
+/// succeeded.
+pub fn run(revs: &[(Submodule, String)], args: &[OsString]) -> io::Result<bool> {
+    let workspace = Path::new(WORKSPACE).canonicalize()?;
+    let dir = revisions_dir();
+    fs::create_dir_all(&dir)?;
+    let dir = dir.canonicalize()?;
+
+    let mut revisions: Vec<(Revision, PathBuf)> = Vec::new();
+    for (submodule, rev) in revs {
+        let revision = Revision::resolve(&workspace, *submodule, rev)?;
+        if revisions.iter().any(|(other, _)| *other == revision) {
+            continue;
+        }
+        let worktree = checkout(&workspace, &dir, &revision)?;
+        revisions.push((revision, worktree));
+    }
+    let manifest = generate(&workspace, &dir, &revisions)?;
+
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(["run", "--release", "--quiet", "--manifest-path"])
+        .arg(&manifest)
+        .arg("--")
+        .args(args)
+        .status()?;
+    Ok(status.success())
+}
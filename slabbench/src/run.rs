@@ -0,0 +1,391 @@
+//! Running workloads outside of Criterion, as the `slabbench` binary does.
+//!
+//! Each iteration runs the workload's setup untimed and times only the
+//! measured part with [`Instant`](std::time::Instant), so results are close to
+//! but not directly comparable with Criterion's estimates. In latency mode,
+//! every operation of the measured part is timed instead; see
+//! [`crate::latency`].
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::audit::Fingerprint;
+use crate::backend::{BackendSet, BackendVisitor, Slabbable};
+use crate::environment::Environment;
+use crate::latency::{self, OpHistograms, OpLatency, Timed};
+use crate::payload::{visit_payload, Payload, PayloadVisitor};
+use crate::phase::{PhaseTime, Phases};
+use crate::scenario::Case;
+
+/// Timing statistics for one scenario case on one backend.
+#[derive(Debug, Clone, Serialize)]
+pub struct Measurement {
+    pub scenario: String,
+    /// The case's label within the scenario; empty if it has only one.
+    pub case: String,
+    /// The case's Criterion parameter, if it is not the size.
+    pub parameter: Option<String>,
+    pub payload: &'static str,
+    pub backend: &'static str,
+    pub size: usize,
+    pub seed: u64,
+    /// The end state of one iteration on this backend, with the operations
+    /// it performed for computing throughput.
+    pub fingerprint: Fingerprint,
+    pub iterations: usize,
+    pub min_ns: u64,
+    pub median_ns: u64,
+    pub mean_ns: u64,
+    pub max_ns: u64,
+    /// Mean time per iteration of each phase of the workload.
+    pub phases: Vec<PhaseTime>,
+}
+
+struct Measure<'a, B> {
+    backends: &'a B,
+    scenario: &'a str,
+    case: &'a Case,
+    backend: &'a str,
+    size: usize,
+    iterations: usize,
+}
+
+impl<B: BackendSet> PayloadVisitor for Measure<'_, B> {
+    type Output = Option<Measurement>;
+
+    fn visit<T: Payload>(self, _name: &'static str) -> Option<Measurement> {
+        let backends = self.backends;
+        backends.visit::<T, _>(self.backend, self)
+    }
+}
+
+impl<T: Payload, B> BackendVisitor<T> for Measure<'_, B> {
+    type Output = Measurement;
+
+    fn visit<S: Slabbable<T>>(self, name: &'static str) -> Measurement {
+        let mut phases = Phases::disabled();
+        let mut times: Vec<u64> = (0..self.iterations)
+            .map(|_| {
+                let elapsed = self.case.workload.run_timed::<T, S>(self.size, self.case.seed, &mut phases);
+                elapsed.as_nanos() as u64
+            })
+            .collect();
+        times.sort_unstable();
+
+        Measurement {
+            scenario: self.scenario.to_string(),
+            case: self.case.label.clone(),
+            parameter: self.case.parameter.clone(),
+            payload: self.case.payload,
+            backend: name,
+            size: self.size,
+            seed: self.case.seed,
+            fingerprint: self.case.workload.fingerprint::<T, S>(self.size, self.case.seed),
+            iterations: self.iterations,
+            min_ns: times[0],
+            median_ns: times[times.len() / 2],
+            mean_ns: times.iter().sum::<u64>() / times.len() as u64,
+            max_ns: times[times.len() - 1],
+            phases: phases.summaries(),
+        }
+    }
+}
+
+/// Runs `case` of the scenario called `scenario` at `size` on the backend
+/// called `backend` in `backends` for `iterations` (at least one)
+/// iterations, or returns `None` if there is no such backend or payload.
+pub fn measure(
+    backends: &impl BackendSet,
+    scenario: &str,
+    case: &Case,
+    backend: &str,
+    size: usize,
+    iterations: usize,
+) -> Option<Measurement> {
+    assert!(iterations > 0, "at least one iteration is required");
+    visit_payload(
+        case.payload,
+        Measure {
+            backends,
+            scenario,
+            case,
+            backend,
+            size,
+            iterations,
+        },
+    )
+    .flatten()
+}
+
+/// Per-operation latency percentiles for one scenario case on one backend.
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    pub scenario: String,
+    pub case: String,
+    pub parameter: Option<String>,
+    pub payload: &'static str,
+    pub backend: &'static str,
+    pub size: usize,
+    pub seed: u64,
+    pub iterations: usize,
+    pub ops: Vec<OpLatency>,
+}
+
+struct MeasureLatency<'a, B> {
+    backends: &'a B,
+    scenario: &'a str,
+    case: &'a Case,
+    backend: &'a str,
+    size: usize,
+    iterations: usize,
+}
+
+impl<B: BackendSet> PayloadVisitor for MeasureLatency<'_, B> {
+    type Output = Option<LatencyReport>;
+
+    fn visit<T: Payload>(self, _name: &'static str) -> Option<LatencyReport> {
+        let backends = self.backends;
+        backends.visit::<T, _>(self.backend, self)
+    }
+}
+
+impl<T: Payload, B> BackendVisitor<T> for MeasureLatency<'_, B> {
+    type Output = LatencyReport;
+
+    fn visit<S: Slabbable<T>>(self, name: &'static str) -> LatencyReport {
+        let workload = &self.case.workload;
+        let mut histograms = OpHistograms::default();
+        for _ in 0..self.iterations {
+            let prepared = workload.setup::<T, Timed<S>>(self.size, self.case.seed);
+            latency::reset();
+            let container = workload.run::<T, Timed<S>>(prepared, self.size);
+            histograms.merge(&latency::take());
+            drop(container);
+        }
+
+        LatencyReport {
+            scenario: self.scenario.to_string(),
+            case: self.case.label.clone(),
+            parameter: self.case.parameter.clone(),
+            payload: self.case.payload,
+            backend: name,
+            size: self.size,
+            seed: self.case.seed,
+            iterations: self.iterations,
+            ops: histograms.summaries(),
+        }
+    }
+}
+
+/// Like [`measure`], but times every operation and reports latency
+/// percentiles per kind of operation.
+pub fn measure_latency(
+    backends: &impl BackendSet,
+    scenario: &str,
+    case: &Case,
+    backend: &str,
+    size: usize,
+    iterations: usize,
+) -> Option<LatencyReport> {
+    assert!(iterations > 0, "at least one iteration is required");
+    visit_payload(
+        case.payload,
+        MeasureLatency {
+            backends,
+            scenario,
+            case,
+            backend,
+            size,
+            iterations,
+        },
+    )
+    .flatten()
+}
+
+/// Formats a duration in nanoseconds with a unit suited to its magnitude,
+/// e.g. `962.95 ns` or `3.92 µs`.
+pub fn format_ns(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{:.2} ns", ns)
+    } else if ns < 1e6 {
+        format!("{:.2} µs", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.2} ms", ns / 1e6)
+    } else {
+        format!("{:.2} s", ns / 1e9)
+    }
+}
+
+/// Writes measurements as an aligned plain-text table.
+pub fn write_table<W: Write>(writer: W, results: &[Measurement]) -> io::Result<()> {
+    let header = [
+        "scenario", "case", "payload", "backend", "size", "iters", "min", "median", "mean", "max", "ops", "median/op",
+    ];
+    let rows: Vec<[String; 12]> = results
+        .iter()
+        .map(|m| {
+            [
+                m.scenario.clone(),
+                match &m.parameter {
+                    Some(parameter) => format!("{}/{}", m.case, parameter),
+                    None => m.case.clone(),
+                },
+                m.payload.to_string(),
+                m.backend.to_string(),
+                m.size.to_string(),
+                m.iterations.to_string(),
+                format_ns(m.min_ns as f64),
+                format_ns(m.median_ns as f64),
+                format_ns(m.mean_ns as f64),
+                format_ns(m.max_ns as f64),
+                m.fingerprint.ops.total().to_string(),
+                format_ns(m.median_ns as f64 / m.fingerprint.ops.total().max(1) as f64),
+            ]
+        })
+        .collect();
+
+    write_aligned(writer, &header, &rows, 4)
+}
+
+/// Writes `rows` under `header` in aligned columns. The first `text_columns`
+/// columns are left-aligned and the rest, which are numeric, right-aligned.
+pub(crate) fn write_aligned<W: Write, const N: usize>(
+    writer: W,
+    header: &[&str; N],
+    rows: &[[String; N]],
+    text_columns: usize,
+) -> io::Result<()> {
+    write_styled(writer, header, rows, TableStyle::Plain { text_columns })
+}
+
+/// Writes `rows` under `header` as a Markdown table, with every column
+/// left-aligned and padded to the same width in each row.
+pub(crate) fn write_markdown<W: Write, const N: usize>(
+    writer: W,
+    header: &[&str; N],
+    rows: &[[String; N]],
+) -> io::Result<()> {
+    write_styled(writer, header, rows, TableStyle::Markdown)
+}
+
+#[derive(Clone, Copy)]
+enum TableStyle {
+    Plain { text_columns: usize },
+    Markdown,
+}
+
+fn write_styled<W: Write, const N: usize>(
+    mut writer: W,
+    header: &[&str; N],
+    rows: &[[String; N]],
+    style: TableStyle,
+) -> io::Result<()> {
+    let mut widths = header.map(|h| h.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let write_row = |writer: &mut W, cells: &[&str]| -> io::Result<()> {
+        for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+            let padding = width - cell.chars().count();
+            match style {
+                TableStyle::Plain { text_columns } => {
+                    if i < text_columns {
+                        write!(writer, "{}{}", cell, " ".repeat(padding))?;
+                    } else {
+                        write!(writer, "{}{}", " ".repeat(padding), cell)?;
+                    }
+                    write!(writer, "{}", if i + 1 < cells.len() { "  " } else { "\n" })?;
+                }
+                TableStyle::Markdown => write!(writer, "| {}{} ", cell, " ".repeat(padding))?,
+            }
+        }
+        if let TableStyle::Markdown = style {
+            writeln!(writer, "|")?;
+        }
+        Ok(())
+    };
+
+    write_row(&mut writer, header)?;
+    if let TableStyle::Markdown = style {
+        let rules = widths.map(|width| "-".repeat(width));
+        write_row(&mut writer, &rules.each_ref().map(String::as_str))?;
+    }
+    for row in rows {
+        write_row(&mut writer, &row.each_ref().map(String::as_str))?;
+    }
+    Ok(())
+}
+
+/// Writes the phase breakdown of measurements as an aligned plain-text table,
+/// one row per phase.
+pub fn write_phase_table<W: Write>(writer: W, results: &[Measurement]) -> io::Result<()> {
+    let header = ["scenario", "case", "payload", "backend", "phase", "size", "mean", "share"];
+    let mut rows: Vec<[String; 8]> = Vec::new();
+    for m in results {
+        let case = match &m.parameter {
+            Some(parameter) => format!("{}/{}", m.case, parameter),
+            None => m.case.clone(),
+        };
+        for phase in &m.phases {
+            rows.push([
+                m.scenario.clone(),
+                case.clone(),
+                m.payload.to_string(),
+                m.backend.to_string(),
+                phase.phase.to_string(),
+                m.size.to_string(),
+                format_ns(phase.mean_ns as f64),
+                format!("{:.1}%", phase.share * 100.0),
+            ]);
+        }
+    }
+
+    write_aligned(writer, &header, &rows, 5)
+}
+
+/// Writes latency reports as an aligned plain-text table, one row per kind
+/// of operation.
+pub fn write_latency_table<W: Write>(writer: W, reports: &[LatencyReport]) -> io::Result<()> {
+    let header = ["scenario", "case", "payload", "backend", "op", "size", "count", "p50", "p99", "p99.9", "max"];
+    let mut rows: Vec<[String; 11]> = Vec::new();
+    for report in reports {
+        let case = match &report.parameter {
+            Some(parameter) => format!("{}/{}", report.case, parameter),
+            None => report.case.clone(),
+        };
+        for op in &report.ops {
+            rows.push([
+                report.scenario.clone(),
+                case.clone(),
+                report.payload.to_string(),
+                report.backend.to_string(),
+                op.op.to_string(),
+                report.size.to_string(),
+                op.count.to_string(),
+                format_ns(op.p50_ns as f64),
+                format_ns(op.p99_ns as f64),
+                format_ns(op.p999_ns as f64),
+                format_ns(op.max_ns as f64),
+            ]);
+        }
+    }
+
+    write_aligned(writer, &header, &rows, 5)
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a, R> {
+    environment: &'a Environment,
+    results: &'a [R],
+}
+
+/// Writes measurements or latency reports as pretty-printed JSON, together
+/// with the environment they were measured in.
+pub fn write_json<W: Write, R: Serialize>(mut writer: W, environment: &Environment, results: &[R]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut writer, &JsonOutput { environment, results })?;
+    writeln!(writer)
+}
@@ -0,0 +1,446 @@
+//! Benchmark scenarios described in JSON files.
+//!
+//! A scenario names a workload, its parameters, and the sizes and seeds to
+//! run it at. The Criterion bench and the `slabbench` binary both load every
+//! `*.json` file in [`DEFAULT_DIR`], so a new scenario is added by dropping in
+//! a file. The file stem becomes the scenario's name, which is also its
+//! Criterion group. For example, `high_churn_workload.json`:
+//!
+//! ```json
+//! {
+//!     "description": "Repeated cycles of removal and reinsertion",
+//!     "sample_size": 30,
+//!     "sizes": [1000, 5000, 10000, 50000],
+//!     "workload": {
+//!         "kind": "high_churn",
+//!         "patterns": ["uniform", "clustered", "random"],
+//!         "cycles": 20,
+//!         "reinsert_ratio": 0.5,
+//!         "fresh_insert_ratio": 0.1
+//!     }
+//! }
+//! ```
+//!
+//! `sample_size` defaults to 20, `seeds` to `[42]` and `payloads` to
+//! `["usize"]`. Seeds only apply to cases that use a PRNG: the `random` churn
+//! pattern and `generated` workloads, whose parameters are those of
+//! [`WorkloadSpec`](crate::generator::WorkloadSpec) with the target occupancy
+//! set to the size and the operation count given per element. Every case is
+//! run once per payload in [`PAYLOADS`](crate::payload::PAYLOADS).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::generator::{KeyDistribution, WorkloadSpec};
+use crate::payload::PAYLOADS;
+use crate::workload::{
+    ChurnPattern, Compaction, HighChurn, HoleLayout, IterMode, Iteration, SparseAccess, StandardMixed, Workload,
+};
+
+/// The checked-in scenario directory.
+pub const DEFAULT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+
+/// A workload plus the sizes and seeds to run it at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// The file stem of the scenario file, used as the Criterion group name.
+    #[serde(skip)]
+    pub name: String,
+    /// A one-line summary shown by `slabbench list`.
+    #[serde(default)]
+    pub description: String,
+    /// Criterion sample size (at least 10).
+    #[serde(default = "default_sample_size")]
+    pub sample_size: usize,
+    /// Collection sizes to run the workload at.
+    pub sizes: Vec<usize>,
+    /// Seeds for cases that use a PRNG.
+    #[serde(default = "default_seeds")]
+    pub seeds: Vec<u64>,
+    /// Element types to store, by name.
+    #[serde(default = "default_payloads")]
+    pub payloads: Vec<String>,
+    pub workload: ScenarioWorkload,
+}
+
+fn default_sample_size() -> usize {
+    20
+}
+
+fn default_seeds() -> Vec<u64> {
+    vec![42]
+}
+
+fn default_payloads() -> Vec<String> {
+    vec!["usize".to_string()]
+}
+
+/// The workload of a [`Scenario`], tagged by `kind`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScenarioWorkload {
+    StandardMixed {
+        remove_every: usize,
+        fresh_insert_ratio: f64,
+    },
+    /// One case per pattern (and per seed, for seeded patterns).
+    HighChurn {
+        patterns: Vec<ChurnPattern>,
+        cycles: usize,
+        reinsert_ratio: f64,
+        fresh_insert_ratio: f64,
+    },
+    SparseAccess {
+        keep_every: usize,
+        fresh_insert_ratio: f64,
+    },
+    Compaction {
+        keep_every: usize,
+        fresh_inserts: usize,
+    },
+    /// Iteration over a collection of `size` slots with some of them vacant,
+    /// one case per mode, layout and density (and per seed, for the random
+    /// layout). The density in percent is the Criterion parameter.
+    Iteration {
+        /// Fractions of the slots that hold a live element, in `(0, 1]`.
+        densities: Vec<f64>,
+        layouts: Vec<HoleLayout>,
+        modes: Vec<IterMode>,
+    },
+    /// A trace generated from an operation mix, one case per seed.
+    Generated {
+        /// Operations after the initial fill, per element of the size.
+        operations_per_element: f64,
+        insert_percent: f64,
+        remove_percent: f64,
+        get_percent: f64,
+        iterate_percent: f64,
+        remove_distribution: KeyDistribution,
+        get_distribution: KeyDistribution,
+    },
+}
+
+/// One benchmark within a scenario at a given size.
+#[derive(Debug, Clone)]
+pub struct Case {
+    /// Distinguishes cases of the same scenario, e.g. `clustered` or
+    /// `random_seed42`; empty if the scenario has a single case.
+    pub label: String,
+    /// The Criterion parameter, if it is not the size; e.g. the density in
+    /// percent for iteration cases.
+    pub parameter: Option<String>,
+    /// The name of the payload type, one of [`PAYLOADS`].
+    pub payload: &'static str,
+    pub seed: u64,
+    pub workload: Workload,
+}
+
+impl Case {
+    /// The benchmark ID of this case on `backend`. The payload is appended
+    /// unless it is the default `usize`, so existing IDs stay stable.
+    pub fn id(&self, backend: &str) -> String {
+        let mut id = backend.to_string();
+        for part in [self.label.as_str(), self.payload] {
+            if !part.is_empty() && part != "usize" {
+                id.push('_');
+                id.push_str(part);
+            }
+        }
+        id
+    }
+
+    /// The Criterion parameter of this case at `size`.
+    pub fn parameter(&self, size: usize) -> String {
+        self.parameter.clone().unwrap_or_else(|| size.to_string())
+    }
+}
+
+impl Scenario {
+    /// Loads and validates the scenario file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let context =
+            |e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e));
+
+        let mut scenario: Scenario = serde_json::from_slice(&fs::read(path)?).map_err(|e| context(&e))?;
+        scenario.name = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+        scenario.validate().map_err(|e| context(&e))?;
+        Ok(scenario)
+    }
+
+    /// Loads every `*.json` file in `dir`, sorted by name.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<Self>> {
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths.iter().map(Self::load).collect()
+    }
+
+    /// Checks that the scenario can be run.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sizes.is_empty() {
+            return Err("at least one size is required".to_string());
+        }
+        if self.seeds.is_empty() {
+            return Err("at least one seed is required".to_string());
+        }
+        if self.sample_size < 10 {
+            return Err(format!("sample_size must be at least 10, got {}", self.sample_size));
+        }
+        if self.payloads.is_empty() {
+            return Err("at least one payload is required".to_string());
+        }
+        if let Some(payload) = self.payloads.iter().find(|payload| !PAYLOADS.contains(&payload.as_str())) {
+            return Err(format!("unknown payload '{}', expected one of {}", payload, PAYLOADS.join(", ")));
+        }
+
+        let ratios = match &self.workload {
+            ScenarioWorkload::StandardMixed {
+                remove_every,
+                fresh_insert_ratio,
+            } => {
+                if *remove_every == 0 {
+                    return Err("remove_every must be positive".to_string());
+                }
+                vec![*fresh_insert_ratio]
+            }
+            ScenarioWorkload::HighChurn {
+                patterns,
+                reinsert_ratio,
+                fresh_insert_ratio,
+                ..
+            } => {
+                if patterns.is_empty() {
+                    return Err("at least one churn pattern is required".to_string());
+                }
+                if *reinsert_ratio > 1.0 {
+                    return Err(format!("reinsert_ratio must be at most 1, got {}", reinsert_ratio));
+                }
+                vec![*reinsert_ratio, *fresh_insert_ratio]
+            }
+            ScenarioWorkload::SparseAccess {
+                keep_every,
+                fresh_insert_ratio,
+            } => {
+                if *keep_every == 0 {
+                    return Err("keep_every must be positive".to_string());
+                }
+                vec![*fresh_insert_ratio]
+            }
+            ScenarioWorkload::Compaction { keep_every, .. } => {
+                if *keep_every == 0 {
+                    return Err("keep_every must be positive".to_string());
+                }
+                Vec::new()
+            }
+            ScenarioWorkload::Iteration {
+                densities,
+                layouts,
+                modes,
+            } => {
+                if densities.is_empty() || layouts.is_empty() || modes.is_empty() {
+                    return Err("at least one density, layout and mode is required".to_string());
+                }
+                if let Some(density) = densities.iter().find(|&&density| density.is_nan() || density <= 0.0 || density > 1.0) {
+                    return Err(format!("densities must be in (0, 1], got {}", density));
+                }
+                Vec::new()
+            }
+            ScenarioWorkload::Generated {
+                operations_per_element,
+                ..
+            } => {
+                self.spec(0).expect("workload is generated").validate()?;
+                vec![*operations_per_element]
+            }
+        };
+        if ratios.iter().any(|&ratio| ratio.is_nan() || ratio < 0.0) {
+            return Err("ratios must be non-negative".to_string());
+        }
+        Ok(())
+    }
+
+    /// The spec a `generated` workload uses at `size`, or `None` for other
+    /// kinds of workload.
+    pub fn spec(&self, size: usize) -> Option<WorkloadSpec> {
+        match self.workload {
+            ScenarioWorkload::Generated {
+                operations_per_element,
+                insert_percent,
+                remove_percent,
+                get_percent,
+                iterate_percent,
+                remove_distribution,
+                get_distribution,
+            } => Some(WorkloadSpec {
+                target_occupancy: size,
+                operations: (size as f64 * operations_per_element) as usize,
+                insert_percent,
+                remove_percent,
+                get_percent,
+                iterate_percent,
+                remove_distribution,
+                get_distribution,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The cases to run at `size`, for every payload. Generated workloads
+    /// produce their traces here, so this is best called outside of timing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a payload is not in [`PAYLOADS`]; see
+    /// [`validate`](Self::validate).
+    pub fn cases(&self, size: usize) -> Vec<Case> {
+        let workload_cases = self.workload_cases(size);
+        let mut cases = Vec::new();
+        for payload in &self.payloads {
+            let payload = *PAYLOADS
+                .iter()
+                .find(|&&name| name == payload)
+                .unwrap_or_else(|| panic!("unknown payload '{}'", payload));
+            for case in &workload_cases {
+                cases.push(Case {
+                    payload,
+                    ..case.clone()
+                });
+            }
+        }
+        cases
+    }
+
+    /// The cases to run at `size`, with the default payload.
+    fn workload_cases(&self, size: usize) -> Vec<Case> {
+        let single = |workload: Workload| {
+            vec![Case {
+                label: String::new(),
+                parameter: None,
+                payload: "usize",
+                seed: 0,
+                workload,
+            }]
+        };
+
+        match &self.workload {
+            ScenarioWorkload::StandardMixed {
+                remove_every,
+                fresh_insert_ratio,
+            } => single(Workload::StandardMixed(StandardMixed {
+                remove_every: *remove_every,
+                fresh_insert_ratio: *fresh_insert_ratio,
+            })),
+            ScenarioWorkload::HighChurn {
+                patterns,
+                cycles,
+                reinsert_ratio,
+                fresh_insert_ratio,
+            } => {
+                let mut cases = Vec::new();
+                for &pattern in patterns {
+                    let workload = Workload::HighChurn(HighChurn {
+                        pattern,
+                        cycles: *cycles,
+                        reinsert_ratio: *reinsert_ratio,
+                        fresh_insert_ratio: *fresh_insert_ratio,
+                    });
+                    // Only seeded patterns get one case per seed, with the
+                    // seed recorded in the label
+                    if pattern.is_seeded() {
+                        for &seed in &self.seeds {
+                            cases.push(Case {
+                                label: format!("{}_seed{}", pattern.name(), seed),
+                                parameter: None,
+                                payload: "usize",
+                                seed,
+                                workload: workload.clone(),
+                            });
+                        }
+                    } else {
+                        cases.push(Case {
+                            label: pattern.name().to_string(),
+                            parameter: None,
+                            payload: "usize",
+                            seed: 0,
+                            workload,
+                        });
+                    }
+                }
+                cases
+            }
+            ScenarioWorkload::SparseAccess {
+                keep_every,
+                fresh_insert_ratio,
+            } => single(Workload::SparseAccess(SparseAccess {
+                keep_every: *keep_every,
+                fresh_insert_ratio: *fresh_insert_ratio,
+            })),
+            ScenarioWorkload::Compaction {
+                keep_every,
+                fresh_inserts,
+            } => single(Workload::Compaction(Compaction {
+                keep_every: *keep_every,
+                fresh_inserts: *fresh_inserts,
+            })),
+            ScenarioWorkload::Iteration {
+                densities,
+                layouts,
+                modes,
+            } => {
+                let mut cases = Vec::new();
+                for &mode in modes {
+                    for &layout in layouts {
+                        // Only the random layout depends on the seed
+                        let seeds = match layout {
+                            HoleLayout::Strided => vec![(String::new(), 0)],
+                            HoleLayout::Random => {
+                                self.seeds.iter().map(|&seed| (format!("_seed{}", seed), seed)).collect()
+                            }
+                        };
+                        for (seed_label, seed) in seeds {
+                            let mut label = format!("{}_{}{}", mode.name(), layout.name(), seed_label);
+                            if self.sizes.len() > 1 {
+                                label = format!("{}_{}slots", label, size);
+                            }
+                            for &density in densities {
+                                cases.push(Case {
+                                    label: label.clone(),
+                                    // Rounded to hundredths so e.g. 0.3 is `30`, not `30.000000000000004`
+                                    parameter: Some(format!("{}", (density * 1e4).round() / 100.0)),
+                                    payload: "usize",
+                                    seed,
+                                    workload: Workload::Iteration(Iteration { density, layout, mode }),
+                                });
+                            }
+                        }
+                    }
+                }
+                cases
+            }
+            ScenarioWorkload::Generated { .. } => {
+                let spec = self.spec(size).expect("workload is generated");
+                self.seeds
+                    .iter()
+                    .map(|&seed| Case {
+                        label: format!("seed{}", seed),
+                        parameter: None,
+                        payload: "usize",
+                        seed,
+                        workload: Workload::Replay(Arc::new(spec.generate(seed))),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,636 @@
+//! Recording and replaying streams of slab operations.
+//!
+//! A [`Trace`] is a sequence of [`Op`]s captured from a real workload by
+//! wrapping its backend in a [`Recorder`]. Traces can be stored in a compact
+//! binary format or a human-readable text format, and replayed against any
+//! [`Slabbable`] backend.
+//!
+//! Keys in a trace are the keys the *recorded* backend handed out. Backends do
+//! not agree on which key `insert` returns, so [`Trace::replay`] maps each
+//! recorded key to whatever key the replaying backend returned for the same
+//! insert. Keys that were never returned by an insert (e.g. a lookup of a key
+//! that was never valid, or an `insert_at` of a fresh key) are used as-is.
+//! Replaying an `insert_at` of a fresh key grows the backend up to that key,
+//! so loading a trace rejects fresh `insert_at` keys more than
+//! [`MAX_KEY_SLACK`] past its number of operations.
+//!
+//! # Binary format
+//!
+//! The magic bytes `SLBT`, a version byte (currently 1), then one record per
+//! operation until end of file: an opcode byte, followed for keyed operations
+//! by the key as an unsigned LEB128 varint.
+//!
+//! # Text format
+//!
+//! A `slabtrace v1` header line, then one operation per line: `insert <key>`,
+//! `insert_at <key>`, `remove <key>`, `get <key>`, `iter`, `shrink` or
+//! `clear`. Blank lines and lines starting with `#` are ignored.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::backend::Slabbable;
+use crate::payload::Payload;
+
+/// Magic bytes at the start of a binary trace.
+pub const MAGIC: [u8; 4] = *b"SLBT";
+
+/// Version of the trace formats written by this crate.
+pub const VERSION: u8 = 1;
+
+/// First line of a text trace.
+pub const TEXT_HEADER: &str = "slabtrace v1";
+
+/// How far past a trace's number of operations the fresh key of an
+/// `insert_at` may lie. A recorded backend hands out keys below its number of
+/// slots, so anything further out is taken to be corrupt.
+pub const MAX_KEY_SLACK: usize = 1 << 16;
+
+const OP_INSERT: u8 = 0;
+const OP_INSERT_AT: u8 = 1;
+const OP_REMOVE: u8 = 2;
+const OP_GET: u8 = 3;
+const OP_ITERATE: u8 = 4;
+const OP_SHRINK: u8 = 5;
+const OP_CLEAR: u8 = 6;
+
+/// A single recorded operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// `insert`, which returned `key` on the recorded backend.
+    Insert { key: usize },
+    /// `insert_at` at `key`.
+    InsertAt { key: usize },
+    /// `remove` of `key`.
+    Remove { key: usize },
+    /// A lookup of `key` (`get`, `get_mut` or `contains`).
+    Get { key: usize },
+    /// A full iteration over all elements.
+    Iterate,
+    /// `shrink`.
+    Shrink,
+    /// `clear`.
+    Clear,
+}
+
+impl Op {
+    fn opcode(self) -> u8 {
+        match self {
+            Op::Insert { .. } => OP_INSERT,
+            Op::InsertAt { .. } => OP_INSERT_AT,
+            Op::Remove { .. } => OP_REMOVE,
+            Op::Get { .. } => OP_GET,
+            Op::Iterate => OP_ITERATE,
+            Op::Shrink => OP_SHRINK,
+            Op::Clear => OP_CLEAR,
+        }
+    }
+
+    fn key(self) -> Option<usize> {
+        match self {
+            Op::Insert { key } | Op::InsertAt { key } | Op::Remove { key } | Op::Get { key } => Some(key),
+            Op::Iterate | Op::Shrink | Op::Clear => None,
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Op::Insert { key } => write!(f, "insert {}", key),
+            Op::InsertAt { key } => write!(f, "insert_at {}", key),
+            Op::Remove { key } => write!(f, "remove {}", key),
+            Op::Get { key } => write!(f, "get {}", key),
+            Op::Iterate => f.write_str("iter"),
+            Op::Shrink => f.write_str("shrink"),
+            Op::Clear => f.write_str("clear"),
+        }
+    }
+}
+
+impl std::str::FromStr for Op {
+    type Err = io::Error;
+
+    fn from_str(line: &str) -> io::Result<Self> {
+        let mut parts = line.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let key = match parts.next() {
+            Some(key) => Some(key.parse::<usize>().map_err(|e| invalid_data(format!("bad key in {:?}: {}", line, e)))?),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(invalid_data(format!("trailing input in {:?}", line)));
+        }
+
+        let op = match (name, key) {
+            ("insert", Some(key)) => Op::Insert { key },
+            ("insert_at", Some(key)) => Op::InsertAt { key },
+            ("remove", Some(key)) => Op::Remove { key },
+            ("get", Some(key)) => Op::Get { key },
+            ("iter", None) => Op::Iterate,
+            ("shrink", None) => Op::Shrink,
+            ("clear", None) => Op::Clear,
+            _ => return Err(invalid_data(format!("unknown operation {:?}", line))),
+        };
+        Ok(op)
+    }
+}
+
+/// A recorded sequence of operations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub ops: Vec<Op>,
+}
+
+impl Trace {
+    /// Creates an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an operation to the trace.
+    pub fn push(&mut self, op: Op) {
+        self.ops.push(op);
+    }
+
+    /// Writes the trace in the binary format.
+    pub fn write_binary<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for &op in &self.ops {
+            writer.write_all(&[op.opcode()])?;
+            if let Some(key) = op.key() {
+                write_varint(&mut writer, key as u64)?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads a trace in the binary format.
+    pub fn read_binary<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut bytes = reader.bytes();
+        let mut next = move || bytes.next().transpose();
+
+        let mut magic = [0; 4];
+        for byte in &mut magic {
+            *byte = next()?.ok_or_else(|| invalid_data("truncated trace header"))?;
+        }
+        if magic != MAGIC {
+            return Err(invalid_data("not a binary slab trace"));
+        }
+        match next()? {
+            Some(VERSION) => {}
+            Some(version) => return Err(invalid_data(format!("unsupported trace version {}", version))),
+            None => return Err(invalid_data("truncated trace header")),
+        }
+
+        let mut trace = Self::new();
+        while let Some(opcode) = next()? {
+            let mut key = || -> io::Result<usize> {
+                let key = read_varint(&mut next)?;
+                usize::try_from(key).map_err(|_| invalid_data(format!("key {} does not fit in usize", key)))
+            };
+            let op = match opcode {
+                OP_INSERT => Op::Insert { key: key()? },
+                OP_INSERT_AT => Op::InsertAt { key: key()? },
+                OP_REMOVE => Op::Remove { key: key()? },
+                OP_GET => Op::Get { key: key()? },
+                OP_ITERATE => Op::Iterate,
+                OP_SHRINK => Op::Shrink,
+                OP_CLEAR => Op::Clear,
+                _ => return Err(invalid_data(format!("unknown opcode {}", opcode))),
+            };
+            trace.push(op);
+        }
+        trace.validate()?;
+        Ok(trace)
+    }
+
+    /// Writes the trace in the text format.
+    pub fn write_text<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", TEXT_HEADER)?;
+        for op in &self.ops {
+            writeln!(writer, "{}", op)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a trace in the text format.
+    pub fn read_text<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        match lines.next().transpose()? {
+            Some(header) if header.trim() == TEXT_HEADER => {}
+            Some(header) => return Err(invalid_data(format!("unsupported trace header {:?}", header))),
+            None => return Err(invalid_data("empty trace")),
+        }
+
+        let mut trace = Self::new();
+        for line in lines {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            trace.push(line.parse()?);
+        }
+        trace.validate()?;
+        Ok(trace)
+    }
+
+    /// Checks that every `insert_at` of a fresh key lies within
+    /// [`MAX_KEY_SLACK`] of the number of operations, so that replaying the
+    /// trace cannot grow a backend without bound.
+    pub fn validate(&self) -> io::Result<()> {
+        let limit = self.ops.len().saturating_add(MAX_KEY_SLACK);
+        let mut bound = HashSet::new();
+        for (i, &op) in self.ops.iter().enumerate() {
+            match op {
+                Op::Insert { key } => {
+                    bound.insert(key);
+                }
+                Op::InsertAt { key } if !bound.contains(&key) => {
+                    if key > limit {
+                        return Err(invalid_data(format!(
+                            "operation {} inserts at key {}, beyond the limit of {}",
+                            i, key, limit
+                        )));
+                    }
+                    bound.insert(key);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a trace from `path`, detecting whether it is binary or text.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if reader.fill_buf()?.starts_with(&MAGIC) {
+            Self::read_binary(reader)
+        } else {
+            Self::read_text(reader)
+        }
+    }
+
+    /// Saves the trace to `path` in the binary format.
+    pub fn save_binary(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_binary(BufWriter::new(File::create(path)?))
+    }
+
+    /// Saves the trace to `path` in the text format.
+    pub fn save_text(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_text(BufWriter::new(File::create(path)?))
+    }
+
+    /// Drives `backend` through every operation in the trace.
+    ///
+    /// Inserted values are built from the index of the inserting operation.
+    /// Traces that were not read by this module should be checked with
+    /// [`validate`](Self::validate) first.
+    /// Returns the sum of the checksums of all values observed by removals,
+    /// lookups and iterations, so the caller can `black_box` it.
+    pub fn replay<T: Payload, S: Slabbable<T>>(&self, backend: &mut S) -> usize {
+        // Recorded key -> key on this backend. Recorded keys come from the
+        // trace file, so they are not used to size anything
+        let mut keys: HashMap<usize, usize> = HashMap::new();
+        let resolve = |keys: &HashMap<usize, usize>, key: usize| keys.get(&key).copied().unwrap_or(key);
+
+        let mut sum = 0usize;
+        for (i, &op) in self.ops.iter().enumerate() {
+            match op {
+                Op::Insert { key } => {
+                    let actual = backend.insert(T::from_index(i));
+                    keys.insert(key, actual);
+                }
+                Op::InsertAt { key } => {
+                    let actual = resolve(&keys, key);
+                    backend.insert_at(actual, T::from_index(i));
+                    keys.insert(key, actual);
+                }
+                Op::Remove { key } => {
+                    if let Some(val) = backend.remove(resolve(&keys, key)) {
+                        sum = sum.wrapping_add(val.checksum());
+                    }
+                }
+                Op::Get { key } => {
+                    if let Some(val) = backend.get(resolve(&keys, key)) {
+                        sum = sum.wrapping_add(val.checksum());
+                    }
+                }
+                Op::Iterate => {
+                    for (_, val) in backend.iter() {
+                        sum = sum.wrapping_add(val.checksum());
+                    }
+                }
+                Op::Shrink => backend.shrink(),
+                Op::Clear => backend.clear(),
+            }
+        }
+        sum
+    }
+}
+
+impl FromIterator<Op> for Trace {
+    fn from_iter<I: IntoIterator<Item = Op>>(iter: I) -> Self {
+        Self {
+            ops: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// A [`Slabbable`] wrapper that records every operation performed on it.
+///
+/// `get`, `get_mut` and `contains` are all recorded as [`Op::Get`], and every
+/// kind of iteration as [`Op::Iterate`]; `len`, `capacity`, `is_empty` and
+/// `vacant_key` are not recorded.
+#[derive(Debug, Default)]
+pub struct Recorder<S> {
+    inner: S,
+    trace: RefCell<Trace>,
+}
+
+impl<S> Recorder<S> {
+    /// Wraps `inner`, starting with an empty trace.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            trace: RefCell::new(Trace::new()),
+        }
+    }
+
+    /// Returns the wrapped backend.
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Takes the trace recorded so far, leaving an empty one in its place.
+    pub fn take_trace(&mut self) -> Trace {
+        self.trace.take()
+    }
+
+    /// Unwraps the recorder into the backend and the recorded trace.
+    pub fn into_parts(self) -> (S, Trace) {
+        (self.inner, self.trace.into_inner())
+    }
+
+    fn record(&self, op: Op) {
+        self.trace.borrow_mut().push(op);
+    }
+}
+
+impl<T: Default, S: Slabbable<T>> Slabbable<T> for Recorder<S> {
+    fn new_with_capacity(capacity: usize) -> Self {
+        Self::new(S::new_with_capacity(capacity))
+    }
+
+    fn insert(&mut self, value: T) -> usize {
+        let key = self.inner.insert(value);
+        self.record(Op::Insert { key });
+        key
+    }
+
+    fn vacant_key(&self) -> usize {
+        self.inner.vacant_key()
+    }
+
+    fn insert_at(&mut self, key: usize, value: T) -> Option<T> {
+        self.record(Op::InsertAt { key });
+        self.inner.insert_at(key, value)
+    }
+
+    fn remove(&mut self, key: usize) -> Option<T> {
+        self.record(Op::Remove { key });
+        self.inner.remove(key)
+    }
+
+    fn get(&self, key: usize) -> Option<&T> {
+        self.record(Op::Get { key });
+        self.inner.get(key)
+    }
+
+    fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        self.record(Op::Get { key });
+        self.inner.get_mut(key)
+    }
+
+    fn contains(&self, key: usize) -> bool {
+        self.record(Op::Get { key });
+        self.inner.contains(key)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, &'a T)>
+    where
+        T: 'a,
+    {
+        self.record(Op::Iterate);
+        self.inner.iter()
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (usize, &'a mut T)>
+    where
+        T: 'a,
+    {
+        self.record(Op::Iterate);
+        self.inner.iter_mut()
+    }
+
+    fn clear(&mut self) {
+        self.record(Op::Clear);
+        self.inner.clear()
+    }
+
+    fn shrink(&mut self) {
+        self.record(Op::Shrink);
+        self.inner.shrink()
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(next: &mut impl FnMut() -> io::Result<Option<u8>>) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = next()?.ok_or_else(|| invalid_data("truncated varint"))?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("varint too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{visit_backend, BTreeMapWrapper, BackendVisitor, SlabWrapper, BACKENDS};
+
+    fn binary(trace: &Trace) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        trace.write_binary(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn sample() -> Trace {
+        [
+            Op::Insert { key: 0 },
+            Op::Insert { key: 1 },
+            Op::InsertAt { key: 300 },
+            Op::Get { key: 1 },
+            Op::Remove { key: 0 },
+            Op::Iterate,
+            Op::Shrink,
+            Op::Clear,
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn varint_encoding() {
+        let cases: [(usize, &[u8]); 6] = [
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (300, &[0xac, 0x02]),
+            (16383, &[0xff, 0x7f]),
+            (16384, &[0x80, 0x80, 0x01]),
+        ];
+        for (key, encoded) in cases {
+            let trace: Trace = [Op::Get { key }].into_iter().collect();
+            let bytes = binary(&trace);
+            assert_eq!(&bytes[6..], encoded, "key {}", key);
+            assert_eq!(Trace::read_binary(&bytes[..]).unwrap(), trace);
+        }
+
+        let trace: Trace = [Op::Get { key: usize::MAX }].into_iter().collect();
+        assert_eq!(Trace::read_binary(&binary(&trace)[..]).unwrap(), trace);
+    }
+
+    #[test]
+    fn malformed_varints_are_rejected() {
+        let mut truncated = binary(&[Op::Get { key: 128 }].into_iter().collect());
+        truncated.pop();
+        assert!(Trace::read_binary(&truncated[..]).is_err());
+
+        let mut too_long = binary(&Trace::new());
+        too_long.push(OP_GET);
+        too_long.extend([0x80; 10]);
+        too_long.push(0x01);
+        assert!(Trace::read_binary(&too_long[..]).is_err());
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let trace = sample();
+        assert_eq!(Trace::read_binary(&binary(&trace)[..]).unwrap(), trace);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let trace = sample();
+        let mut text = Vec::new();
+        trace.write_text(&mut text).unwrap();
+        assert_eq!(Trace::read_text(&text[..]).unwrap(), trace);
+    }
+
+    #[test]
+    fn replay_maps_recorded_keys() {
+        let mut recorder = Recorder::new(BTreeMapWrapper::<usize>::new_with_capacity(0));
+        let a = recorder.insert(10);
+        let b = recorder.insert(20);
+        recorder.remove(a);
+        recorder.get(b);
+        let (_, trace) = recorder.into_parts();
+
+        // The recorded keys are nowhere near the ones the slab hands out
+        let trace: Trace = trace
+            .ops
+            .into_iter()
+            .map(|op| match op {
+                Op::Insert { key } => Op::Insert { key: usize::MAX - key },
+                Op::Remove { key } => Op::Remove { key: usize::MAX - key },
+                Op::Get { key } => Op::Get { key: usize::MAX - key },
+                op => op,
+            })
+            .collect();
+        let mut slab = SlabWrapper::<usize>::new_with_capacity(0);
+        // The removal sees the value of op 0, the lookup that of op 1
+        assert_eq!(trace.replay(&mut slab), 1);
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn fresh_insert_at_replays_on_every_backend() {
+        struct Replay<'a>(&'a Trace);
+
+        impl BackendVisitor<usize> for Replay<'_> {
+            type Output = (usize, Vec<usize>);
+
+            fn visit<S: Slabbable<usize>>(self, _name: &'static str) -> Self::Output {
+                let mut backend = S::new_with_capacity(0);
+                let sum = self.0.replay(&mut backend);
+                let mut values: Vec<usize> = backend.values().copied().collect();
+                values.sort_unstable();
+                (sum, values)
+            }
+        }
+
+        let trace: Trace = [
+            Op::Insert { key: 0 },
+            Op::InsertAt { key: 300 },
+            Op::Get { key: 300 },
+            Op::InsertAt { key: 5 },
+            Op::Remove { key: 0 },
+            Op::Iterate,
+        ]
+        .into_iter()
+        .collect();
+        for name in BACKENDS {
+            let outcome = visit_backend(name, Replay(&trace)).unwrap();
+            assert_eq!(outcome, (1 + 1 + 3, vec![1, 3]), "{}", name);
+        }
+    }
+
+    #[test]
+    fn distant_insert_at_keys_are_rejected() {
+        let limit = 2 + MAX_KEY_SLACK;
+        let within: Trace = [Op::Insert { key: 0 }, Op::InsertAt { key: limit }].into_iter().collect();
+        assert!(within.validate().is_ok());
+        // A key bound by an earlier insert is mapped rather than grown to
+        let bound: Trace = [Op::Insert { key: usize::MAX }, Op::InsertAt { key: usize::MAX }].into_iter().collect();
+        assert!(bound.validate().is_ok());
+
+        let beyond: Trace = [Op::Insert { key: 0 }, Op::InsertAt { key: limit + 1 }].into_iter().collect();
+        assert!(beyond.validate().is_err());
+        assert!(Trace::read_binary(&binary(&beyond)[..]).is_err());
+        let mut text = Vec::new();
+        beyond.write_text(&mut text).unwrap();
+        assert!(Trace::read_text(&text[..]).is_err());
+    }
+}
@@ -0,0 +1,626 @@
+//! The benchmark workloads, written once against the [`Slabbable`] trait.
+//!
+//! Workloads that need a prepared collection are split into a `*_setup`
+//! function, which is excluded from timing, and a function that performs the
+//! measured work. Every workload returns the final collection so the caller
+//! can inspect it or `black_box` it.
+//!
+//! Each workload's tunables live in a parameter struct so that scenarios can
+//! be described in files; see [`crate::scenario`]. Workloads are generic over
+//! the stored [`Payload`]; the values inserted are built from `usize`s, and
+//! lookups sum their checksums.
+//!
+//! Workloads mark the end of each phase on a [`Phases`] tracker, so a run can
+//! be broken down into insert, lookup, removal and iteration time.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use oorandom::Rand64;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::Fingerprint;
+use crate::backend::Slabbable;
+use crate::ops::{self, Counted};
+use crate::payload::Payload;
+use crate::phase::Phases;
+use crate::trace::Trace;
+
+/// How the high churn workload picks which elements to remove each cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChurnPattern {
+    /// Remove every third element.
+    Uniform,
+    /// Remove 25% of elements from a contiguous section.
+    Clustered,
+    /// Remove a third of the elements, chosen by a seeded PRNG.
+    Random,
+}
+
+impl ChurnPattern {
+    /// The name used for this pattern in benchmark IDs and scenario files.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChurnPattern::Uniform => "uniform",
+            ChurnPattern::Clustered => "clustered",
+            ChurnPattern::Random => "random",
+        }
+    }
+
+    /// Whether the pattern depends on the PRNG seed.
+    pub fn is_seeded(self) -> bool {
+        self == ChurnPattern::Random
+    }
+}
+
+/// `ratio` of `count`, rounded down.
+fn fraction_of(count: usize, ratio: f64) -> usize {
+    (count as f64 * ratio) as usize
+}
+
+/// Parameters of [`standard_mixed`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StandardMixed {
+    /// Remove every n-th inserted element.
+    pub remove_every: usize,
+    /// Elements to insert after the removals, as a fraction of the size.
+    pub fresh_insert_ratio: f64,
+}
+
+/// Create an empty collection with room for half of the elements, so that
+/// [`standard_mixed`] has to grow it.
+pub fn standard_mixed_setup<T: Payload, S: Slabbable<T>>(size: usize) -> S {
+    S::new_with_capacity(size / 2)
+}
+
+/// Insert `size` elements, read them all, remove some, insert some more and
+/// iterate.
+pub fn standard_mixed<T: Payload, S: Slabbable<T>>(
+    params: &StandardMixed,
+    mut container: S,
+    size: usize,
+    phases: &mut Phases,
+) -> S {
+    let mut keys = Vec::with_capacity(size);
+
+    // Insert phase
+    for i in 0..size {
+        keys.push(container.insert(T::from_index(i)));
+    }
+    phases.lap("insert");
+
+    // Get phase
+    let mut sum = 0;
+    for &key in &keys {
+        if let Some(val) = container.get(key) {
+            sum += val.checksum();
+        }
+    }
+    black_box(sum);
+    phases.lap("get");
+
+    // Remove every n-th element
+    for &key in keys.iter().step_by(params.remove_every) {
+        container.remove(key);
+    }
+    phases.lap("remove");
+
+    // Insert some new elements
+    for i in 0..fraction_of(size, params.fresh_insert_ratio) {
+        container.insert(T::from_index(i * 100));
+    }
+    phases.lap("reinsert");
+
+    // Final get phase
+    sum = 0;
+    for (_, val) in container.iter() {
+        sum += val.checksum();
+    }
+    black_box(sum);
+    phases.lap("iterate");
+
+    container
+}
+
+/// Parameters of [`high_churn`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HighChurn {
+    pub pattern: ChurnPattern,
+    /// Number of removal and reinsertion cycles.
+    pub cycles: usize,
+    /// Fraction of the removed elements reinserted each cycle.
+    pub reinsert_ratio: f64,
+    /// Fresh elements inserted each cycle, as a fraction of the size (at
+    /// least one).
+    pub fresh_insert_ratio: f64,
+}
+
+/// State for [`high_churn`]: the collection plus key tracking structures.
+pub struct HighChurnState<S> {
+    container: S,
+    active_keys: Vec<usize>,
+    // Removed keys with the checksums of their values
+    removed_keys: Vec<(usize, usize)>,
+    rng: Rand64,
+}
+
+/// Initialize an empty collection and tracking structures for [`high_churn`].
+pub fn high_churn_setup<T: Payload, S: Slabbable<T>>(size: usize, seed: u64) -> HighChurnState<S> {
+    HighChurnState {
+        container: S::new_with_capacity(size),
+        active_keys: Vec::with_capacity(size),
+        removed_keys: Vec::with_capacity(size / 2),
+        // Reseed every run so each sample sees the same sequence
+        rng: Rand64::new(seed as u128),
+    }
+}
+
+/// Fill the collection halfway, then run cycles of removal (following the
+/// pattern), reinsertion, fresh insertion and lookups.
+pub fn high_churn<T: Payload, S: Slabbable<T>>(
+    params: &HighChurn,
+    state: HighChurnState<S>,
+    size: usize,
+    phases: &mut Phases,
+) -> S {
+    let HighChurnState {
+        mut container,
+        mut active_keys,
+        mut removed_keys,
+        mut rng,
+    } = state;
+
+    // First, fill the container halfway
+    for i in 0..(size / 2) {
+        active_keys.push(container.insert(T::from_index(i)));
+    }
+    phases.lap("fill");
+
+    // Now perform high-churn operations in different patterns
+    for cycle in 0..params.cycles {
+        // Each pattern has a different removal strategy
+        match params.pattern {
+            ChurnPattern::Uniform => {
+                // Remove every third element
+                let mut to_remove = Vec::new();
+                for i in (0..active_keys.len()).step_by(3) {
+                    if i < active_keys.len() {
+                        to_remove.push(i);
+                    }
+                }
+
+                // Remove the elements from the end to avoid index shifting
+                for i in to_remove.iter().rev() {
+                    let key = active_keys.swap_remove(*i);
+                    if let Some(val) = container.remove(key) {
+                        removed_keys.push((key, val.checksum()));
+                    }
+                }
+            }
+            ChurnPattern::Clustered => {
+                // Remove elements in clusters (25% of elements from a continuous section)
+                if !active_keys.is_empty() {
+                    let cluster_size = active_keys.len() / 4;
+                    if cluster_size > 0 && active_keys.len() > cluster_size {
+                        let start = (cycle * 17) % (active_keys.len() - cluster_size);
+
+                        for _ in 0..cluster_size {
+                            let key = active_keys.swap_remove(start);
+                            if let Some(val) = container.remove(key) {
+                                removed_keys.push((key, val.checksum()));
+                            }
+                        }
+                    }
+                }
+            }
+            ChurnPattern::Random => {
+                // Remove random elements (using a seeded PRNG)
+                let num_to_remove = active_keys.len() / 3;
+                for _ in 0..num_to_remove {
+                    if !active_keys.is_empty() {
+                        let idx = rng.rand_range(0..active_keys.len() as u64) as usize;
+                        let key = active_keys.swap_remove(idx);
+                        if let Some(val) = container.remove(key) {
+                            removed_keys.push((key, val.checksum()));
+                        }
+                    }
+                }
+            }
+        }
+        phases.lap("remove");
+
+        // Reinsert some elements from the removed set
+        let num_to_reinsert = fraction_of(removed_keys.len(), params.reinsert_ratio);
+        for _ in 0..num_to_reinsert {
+            if let Some((key, val)) = removed_keys.pop() {
+                // Alternate between inserting at specific index and adding new
+                if cycle % 2 == 0 && !container.contains(key) {
+                    // Insert at the specific index if available
+                    if container.insert_at(key, T::from_index(val + 1000)).is_none() {
+                        active_keys.push(key);
+                    }
+                } else {
+                    // Insert new element, getting a new key
+                    active_keys.push(container.insert(T::from_index(val + 2000)));
+                }
+            }
+        }
+        phases.lap("reinsert");
+
+        // Add some fresh elements
+        let num_new = fraction_of(size, params.fresh_insert_ratio).max(1);
+        for i in 0..num_new {
+            active_keys.push(container.insert(T::from_index(i + cycle * 1000)));
+        }
+        phases.lap("fresh_insert");
+
+        // Occasionally access elements randomly to simulate real use
+        let mut sum = 0;
+        for (i, &key) in active_keys.iter().enumerate() {
+            if i % 5 != cycle % 5 {
+                continue;
+            }
+            if let Some(val) = container.get(key) {
+                sum += val.checksum();
+            }
+        }
+        black_box(sum);
+        phases.lap("get");
+    }
+
+    container
+}
+
+/// Parameters of [`sparse_access`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SparseAccess {
+    /// Keep only every n-th element during setup.
+    pub keep_every: usize,
+    /// Elements to insert after reading, as a fraction of the size.
+    pub fresh_insert_ratio: f64,
+}
+
+/// Create a sparse collection of `size` slots by removing all but every n-th
+/// element.
+pub fn sparse_access_setup<T: Payload, S: Slabbable<T>>(params: &SparseAccess, size: usize) -> (S, Vec<usize>) {
+    let mut container = S::new_with_capacity(size);
+    let mut keys = Vec::with_capacity(size);
+
+    for i in 0..size {
+        keys.push(container.insert(T::from_index(i)));
+    }
+
+    // Remove most elements, keeping only every n-th
+    for (i, &key) in keys.iter().enumerate() {
+        if i % params.keep_every != 0 {
+            container.remove(key);
+        }
+    }
+
+    (container, keys)
+}
+
+/// Look up every original key, iterate, then insert some new elements.
+pub fn sparse_access<T: Payload, S: Slabbable<T>>(
+    params: &SparseAccess,
+    state: (S, Vec<usize>),
+    size: usize,
+    phases: &mut Phases,
+) -> S {
+    let (mut container, keys) = state;
+
+    // Random accesses across the sparse structure
+    let mut sum = 0;
+    for &key in &keys {
+        if let Some(val) = container.get(key) {
+            sum += val.checksum();
+        }
+    }
+    black_box(sum);
+    phases.lap("get");
+
+    // Iteration through sparse structure
+    sum = 0;
+    for (_, val) in container.iter() {
+        sum += val.checksum();
+    }
+    black_box(sum);
+    phases.lap("iterate");
+
+    // Insert some new elements in random vacant slots
+    for i in 0..fraction_of(size, params.fresh_insert_ratio) {
+        container.insert(T::from_index(i * 100));
+    }
+    phases.lap("insert");
+
+    container
+}
+
+/// Parameters of [`compaction`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Compaction {
+    /// Keep only every n-th element during setup.
+    pub keep_every: usize,
+    /// Number of elements to insert after compaction.
+    pub fresh_inserts: usize,
+}
+
+/// Create a fragmented collection of `size` slots by removing all but every
+/// n-th element.
+pub fn compaction_setup<T: Payload, S: Slabbable<T>>(params: &Compaction, size: usize) -> S {
+    let mut container = S::new_with_capacity(size);
+    let mut keys = Vec::with_capacity(size);
+
+    for i in 0..size {
+        keys.push(container.insert(T::from_index(i)));
+    }
+
+    // Remove elements to create fragmentation
+    for (i, &key) in keys.iter().enumerate() {
+        if i % params.keep_every != 0 {
+            container.remove(key);
+        }
+    }
+
+    container
+}
+
+/// Shrink the collection, iterate it, then insert some more elements.
+pub fn compaction<T: Payload, S: Slabbable<T>>(params: &Compaction, mut container: S, phases: &mut Phases) -> S {
+    // Benchmark the compaction and operations after compaction
+    container.shrink();
+    phases.lap("shrink");
+
+    // Operations after compaction
+    let mut sum = 0;
+    for (_, val) in container.iter() {
+        sum += val.checksum();
+    }
+    black_box(sum);
+    phases.lap("iterate");
+
+    // Add some more elements after compaction
+    for i in 0..params.fresh_inserts {
+        container.insert(T::from_index(i * 200));
+    }
+    phases.lap("insert");
+
+    container
+}
+
+/// How the vacant slots of the iteration workload are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoleLayout {
+    /// Live elements are spread evenly, e.g. every fourth slot at 25%.
+    Strided,
+    /// Live elements are chosen by a seeded PRNG.
+    Random,
+}
+
+impl HoleLayout {
+    /// The name used for this layout in benchmark IDs and scenario files.
+    pub fn name(self) -> &'static str {
+        match self {
+            HoleLayout::Strided => "strided",
+            HoleLayout::Random => "random",
+        }
+    }
+}
+
+/// Which iterator the iteration workload drains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IterMode {
+    /// [`Slabbable::iter`]
+    Iter,
+    /// [`Slabbable::iter_mut`]
+    IterMut,
+    /// [`Slabbable::keys`]
+    Keys,
+    /// [`Slabbable::values`]
+    Values,
+}
+
+impl IterMode {
+    /// The name used for this mode in benchmark IDs and scenario files.
+    pub fn name(self) -> &'static str {
+        match self {
+            IterMode::Iter => "iter",
+            IterMode::IterMut => "iter_mut",
+            IterMode::Keys => "keys",
+            IterMode::Values => "values",
+        }
+    }
+}
+
+/// Parameters of [`iteration`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Iteration {
+    /// Fraction of the slots that hold a live element, in `(0, 1]`.
+    pub density: f64,
+    pub layout: HoleLayout,
+    pub mode: IterMode,
+}
+
+impl Iteration {
+    /// Number of live elements left in `size` slots.
+    pub fn live(&self, size: usize) -> usize {
+        fraction_of(size, self.density)
+    }
+}
+
+/// Fill `size` slots, then remove elements until only the density's share is
+/// left, following the hole layout.
+pub fn iteration_setup<T: Payload, S: Slabbable<T>>(params: &Iteration, size: usize, seed: u64) -> S {
+    let mut container = S::new_with_capacity(size);
+    let mut keys = Vec::with_capacity(size);
+
+    for i in 0..size {
+        keys.push(container.insert(T::from_index(i)));
+    }
+
+    match params.layout {
+        HoleLayout::Strided => {
+            // Keep a slot whenever the running share of live slots crosses
+            // an integer, which spaces them as evenly as possible
+            for (i, &key) in keys.iter().enumerate() {
+                if fraction_of(i + 1, params.density) == fraction_of(i, params.density) {
+                    container.remove(key);
+                }
+            }
+        }
+        HoleLayout::Random => {
+            // Shuffle the keys and remove all but the first `live`
+            let mut rng = Rand64::new(seed as u128);
+            for i in (1..keys.len()).rev() {
+                let j = rng.rand_range(0..(i + 1) as u64) as usize;
+                keys.swap(i, j);
+            }
+            for &key in &keys[params.live(size)..] {
+                container.remove(key);
+            }
+        }
+    }
+
+    container
+}
+
+/// Drain one iterator over the collection.
+pub fn iteration<T: Payload, S: Slabbable<T>>(params: &Iteration, mut container: S, phases: &mut Phases) -> S {
+    let mut sum = 0;
+    match params.mode {
+        IterMode::Iter => {
+            for (key, val) in container.iter() {
+                sum += key + val.checksum();
+            }
+        }
+        IterMode::IterMut => {
+            for (key, val) in container.iter_mut() {
+                // Hand out the mutable reference so the access can't be weakened
+                sum += key + black_box(val).checksum();
+            }
+        }
+        IterMode::Keys => {
+            for key in container.keys() {
+                sum += key;
+            }
+        }
+        IterMode::Values => {
+            for val in container.values() {
+                sum += val.checksum();
+            }
+        }
+    }
+    black_box(sum);
+    phases.lap("iterate");
+
+    container
+}
+
+/// A fully parameterized workload that can run on any backend.
+#[derive(Debug, Clone)]
+pub enum Workload {
+    StandardMixed(StandardMixed),
+    HighChurn(HighChurn),
+    SparseAccess(SparseAccess),
+    Compaction(Compaction),
+    Iteration(Iteration),
+    /// Replay a recorded or generated trace into an empty collection.
+    Replay(Arc<Trace>),
+}
+
+/// The untimed state produced by [`Workload::setup`].
+pub enum Prepared<S> {
+    StandardMixed(S),
+    HighChurn(HighChurnState<S>),
+    SparseAccess(S, Vec<usize>),
+    Compaction(S),
+    Iteration(S),
+    Replay(S),
+}
+
+impl Workload {
+    /// Runs the workload once on `S` outside of timing, counting the
+    /// operations its measured part performs, and fingerprints the result.
+    /// The operation counts are used for reporting throughput.
+    pub fn fingerprint<T: Payload, S: Slabbable<T>>(&self, size: usize, seed: u64) -> Fingerprint {
+        let prepared = self.setup::<T, Counted<S>>(size, seed);
+        ops::reset();
+        let Counted(container) = self.run::<T, Counted<S>>(prepared, size);
+        Fingerprint::of::<T, S>(&container, ops::take())
+    }
+
+    /// Performs the untimed setup for one run of the workload.
+    pub fn setup<T: Payload, S: Slabbable<T>>(&self, size: usize, seed: u64) -> Prepared<S> {
+        match self {
+            Workload::StandardMixed(_) => Prepared::StandardMixed(standard_mixed_setup::<T, S>(size)),
+            Workload::HighChurn(_) => Prepared::HighChurn(high_churn_setup::<T, S>(size, seed)),
+            Workload::SparseAccess(params) => {
+                let (container, keys) = sparse_access_setup::<T, S>(params, size);
+                Prepared::SparseAccess(container, keys)
+            }
+            Workload::Compaction(params) => Prepared::Compaction(compaction_setup::<T, S>(params, size)),
+            Workload::Iteration(params) => Prepared::Iteration(iteration_setup::<T, S>(params, size, seed)),
+            Workload::Replay(_) => Prepared::Replay(S::new_with_capacity(0)),
+        }
+    }
+
+    /// Performs the measured part of one run of the workload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prepared` came from a different kind of workload.
+    pub fn run<T: Payload, S: Slabbable<T>>(&self, prepared: Prepared<S>, size: usize) -> S {
+        self.run_phased::<T, S>(prepared, size, &mut Phases::disabled())
+    }
+
+    /// Like [`run`](Self::run), but also records the time spent in each
+    /// phase of the workload in `phases`.
+    pub fn run_phased<T: Payload, S: Slabbable<T>>(&self, prepared: Prepared<S>, size: usize, phases: &mut Phases) -> S {
+        match (self, prepared) {
+            (Workload::StandardMixed(params), Prepared::StandardMixed(container)) => {
+                standard_mixed::<T, S>(params, container, size, phases)
+            }
+            (Workload::HighChurn(params), Prepared::HighChurn(state)) => {
+                high_churn::<T, S>(params, state, size, phases)
+            }
+            (Workload::SparseAccess(params), Prepared::SparseAccess(container, keys)) => {
+                sparse_access::<T, S>(params, (container, keys), size, phases)
+            }
+            (Workload::Compaction(params), Prepared::Compaction(container)) => {
+                compaction::<T, S>(params, container, phases)
+            }
+            (Workload::Iteration(params), Prepared::Iteration(container)) => {
+                iteration::<T, S>(params, container, phases)
+            }
+            (Workload::Replay(trace), Prepared::Replay(mut container)) => {
+                black_box(trace.replay::<T, S>(&mut container));
+                phases.lap("replay");
+                container
+            }
+            _ => panic!("collection was prepared for a different workload"),
+        }
+    }
+
+    /// Runs the workload once on `S` and returns the time spent in
+    /// [`run`](Self::run), excluding setup and dropping the result. The time
+    /// of each phase is added to `phases`.
+    pub fn run_timed<T: Payload, S: Slabbable<T>>(&self, size: usize, seed: u64, phases: &mut Phases) -> Duration {
+        let prepared = self.setup::<T, S>(size, seed);
+        let mut run_phases = Phases::new();
+        let start = Instant::now();
+        let container = black_box(self.run_phased::<T, S>(prepared, size, &mut run_phases));
+        let elapsed = start.elapsed();
+        drop(container);
+        phases.merge(&run_phases);
+        elapsed
+    }
+}
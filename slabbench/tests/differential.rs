@@ -0,0 +1,236 @@
+//! Differential tests: drive random operation sequences through every backend
+//! and a `BTreeMap` reference model, checking they agree after every step.
+//!
+//! Backends hand out different keys from `insert`, so each backend gets its
+//! own model, which learns keys from the backend as they are returned. Failing
+//! sequences are shrunk to a minimal reproduction before being reported.
+
+use std::any::type_name;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use oorandom::Rand64;
+use slabbench::backend::{
+    BTreeMapWrapper, HashMapWrapper, InlineStableVecWrapper, ReusingStableVecWrapper, Slabbable, SlabWrapper,
+    StableVecWrapper, VecOptionWrapper,
+};
+use slabbench::trace::Recorder;
+
+const SEEDS: u64 = 256;
+const OPS_PER_SEQUENCE: usize = 200;
+/// Upper bound for raw keys, which keeps `insert_at` from growing backends far
+const MAX_RAW_KEY: u64 = 96;
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    /// The key returned by the n-th insert (modulo the number of inserts so far)
+    Handle(usize),
+    /// A raw key, which may or may not be occupied
+    Key(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Insert(usize),
+    InsertAt(usize, usize),
+    Remove(Target),
+    Get(Target),
+    GetMut(Target, usize),
+    Contains(Target),
+    Shrink,
+    Clear,
+}
+
+fn generate(rng: &mut Rand64, len: usize) -> Vec<Op> {
+    let target = |rng: &mut Rand64| {
+        if rng.rand_range(0..4) == 0 {
+            Target::Key(rng.rand_range(0..MAX_RAW_KEY) as usize)
+        } else {
+            Target::Handle(rng.rand_u64() as usize)
+        }
+    };
+
+    (0..len)
+        .map(|_| match rng.rand_range(0..100) {
+            0..=34 => Op::Insert(rng.rand_u64() as usize),
+            35..=44 => Op::InsertAt(rng.rand_range(0..MAX_RAW_KEY) as usize, rng.rand_u64() as usize),
+            45..=69 => Op::Remove(target(rng)),
+            70..=84 => Op::Get(target(rng)),
+            85..=89 => Op::GetMut(target(rng), rng.rand_u64() as usize),
+            90..=95 => Op::Contains(target(rng)),
+            96..=98 => Op::Shrink,
+            _ => Op::Clear,
+        })
+        .collect()
+}
+
+fn expect_eq<T: PartialEq + Debug>(what: &str, backend: T, model: T) -> Result<(), String> {
+    if backend == model {
+        Ok(())
+    } else {
+        Err(format!("{}: backend gave {:?}, model expected {:?}", what, backend, model))
+    }
+}
+
+/// Runs `ops` against a fresh `S` and a reference model, returning a
+/// description of the first divergence.
+fn check<S: Slabbable<usize>>(ops: &[Op]) -> Result<(), String> {
+    let mut backend = S::new_with_capacity(0);
+    let mut model = BTreeMap::new();
+    let mut handles: Vec<usize> = Vec::new();
+
+    for (step, &op) in ops.iter().enumerate() {
+        let resolve = |handles: &[usize], target| match target {
+            Target::Handle(n) if !handles.is_empty() => handles[n % handles.len()],
+            Target::Handle(n) => n % MAX_RAW_KEY as usize,
+            Target::Key(key) => key,
+        };
+        let context = |what: &str| format!("step {} ({:?}): {}", step, op, what);
+
+        match op {
+            Op::Insert(value) => {
+                let predicted = backend.vacant_key();
+                let key = backend.insert(value);
+                expect_eq(&context("vacant_key"), predicted, key)?;
+                if model.contains_key(&key) {
+                    return Err(context(&format!("insert returned occupied key {}", key)));
+                }
+                model.insert(key, value);
+                handles.push(key);
+            }
+            Op::InsertAt(key, value) => {
+                expect_eq(&context("insert_at"), backend.insert_at(key, value), model.insert(key, value))?;
+                handles.push(key);
+            }
+            Op::Remove(target) => {
+                let key = resolve(&handles, target);
+                expect_eq(&context("remove"), backend.remove(key), model.remove(&key))?;
+            }
+            Op::Get(target) => {
+                let key = resolve(&handles, target);
+                expect_eq(&context("get"), backend.get(key).copied(), model.get(&key).copied())?;
+            }
+            Op::GetMut(target, value) => {
+                let key = resolve(&handles, target);
+                match (backend.get_mut(key), model.get_mut(&key)) {
+                    (Some(slot), Some(expected)) => {
+                        expect_eq(&context("get_mut"), *slot, *expected)?;
+                        *slot = value;
+                        *expected = value;
+                    }
+                    (None, None) => {}
+                    (slot, expected) => expect_eq(&context("get_mut"), slot.copied(), expected.copied())?,
+                }
+            }
+            Op::Contains(target) => {
+                let key = resolve(&handles, target);
+                expect_eq(&context("contains"), backend.contains(key), model.contains_key(&key))?;
+            }
+            Op::Shrink => backend.shrink(),
+            Op::Clear => {
+                backend.clear();
+                model.clear();
+            }
+        }
+
+        expect_eq(&context("len"), backend.len(), model.len())?;
+        if backend.capacity() < backend.len() {
+            return Err(context(&format!("capacity {} below len {}", backend.capacity(), backend.len())));
+        }
+        let mut contents: Vec<(usize, usize)> = backend.iter().map(|(key, &value)| (key, value)).collect();
+        // The other iterators must visit the same elements in the same order
+        let keys: Vec<usize> = contents.iter().map(|&(key, _)| key).collect();
+        let values: Vec<usize> = contents.iter().map(|&(_, value)| value).collect();
+        expect_eq(&context("keys"), backend.keys().collect::<Vec<_>>(), keys)?;
+        expect_eq(&context("values"), backend.values().copied().collect::<Vec<_>>(), values)?;
+        let mutable: Vec<(usize, usize)> = backend.iter_mut().map(|(key, &mut value)| (key, value)).collect();
+        expect_eq(&context("iter_mut"), mutable, contents.clone())?;
+        contents.sort_unstable();
+        let expected: Vec<(usize, usize)> = model.iter().map(|(&key, &value)| (key, value)).collect();
+        expect_eq(&context("iter"), contents, expected)?;
+    }
+
+    Ok(())
+}
+
+/// Removes chunks of `ops`, largest first, for as long as the sequence still fails.
+fn shrink<S: Slabbable<usize>>(mut ops: Vec<Op>) -> Vec<Op> {
+    let mut chunk = ops.len() / 2;
+    while chunk > 0 {
+        let mut removed_any = false;
+        let mut start = 0;
+        while start < ops.len() {
+            let mut candidate = ops.clone();
+            candidate.drain(start..(start + chunk).min(ops.len()));
+            if check::<S>(&candidate).is_err() {
+                ops = candidate;
+                removed_any = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if !removed_any {
+            chunk /= 2;
+        }
+    }
+    ops
+}
+
+fn differential<S: Slabbable<usize>>() {
+    for seed in 0..SEEDS {
+        let ops = generate(&mut Rand64::new(seed as u128), OPS_PER_SEQUENCE);
+        if check::<S>(&ops).is_ok() {
+            continue;
+        }
+
+        let minimal = shrink::<S>(ops);
+        let error = check::<S>(&minimal).unwrap_err();
+        panic!(
+            "{} diverged from the model (seed {}): {}\nminimal failing sequence: {:#?}",
+            type_name::<S>(),
+            seed,
+            error,
+            minimal
+        );
+    }
+}
+
+#[test]
+fn slab_matches_model() {
+    differential::<SlabWrapper<usize>>();
+}
+
+#[test]
+fn stable_vec_matches_model() {
+    differential::<StableVecWrapper<usize>>();
+}
+
+#[test]
+fn stable_vec_reuse_matches_model() {
+    differential::<ReusingStableVecWrapper<usize>>();
+}
+
+#[test]
+fn inline_stable_vec_matches_model() {
+    differential::<InlineStableVecWrapper<usize>>();
+}
+
+#[test]
+fn vec_option_matches_model() {
+    differential::<VecOptionWrapper<usize>>();
+}
+
+#[test]
+fn hash_map_matches_model() {
+    differential::<HashMapWrapper<usize>>();
+}
+
+#[test]
+fn btree_map_matches_model() {
+    differential::<BTreeMapWrapper<usize>>();
+}
+
+#[test]
+fn recorder_matches_model() {
+    differential::<Recorder<SlabWrapper<usize>>>();
+}